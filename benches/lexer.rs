@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+// A vendor-bundle sized stylesheet built from a snippet that exercises most token types
+const SNIPPET: &str = r#"/* button styles */
+.btn-primary, a.md\:flex > .icon-\31 0 + span ~ em {
+    color: #06c;
+    background: url(images/button.png) no-repeat -.5em 1e3px;
+    font-family: "Helvetica Neue", Arial, sans-serif;
+    margin: 0 auto !important;
+    width: calc(100% - 2rem);
+    content: "\201C";
+}
+@media (min-width: 768px) { #main[data-state="open"]:hover { opacity: 50%; } }
+"#;
+
+const TARGET_SIZE: usize = 2 * 1024 * 1024;
+const ITERATIONS: u32 = 10;
+
+fn main() {
+    let input = SNIPPET.repeat(TARGET_SIZE / SNIPPET.len() + 1);
+
+    bench("tokenize", &input, || css_parser::tokenize(&input).count());
+    bench("parse_str", &input, || css_parser::parse_str(&input));
+}
+
+fn bench<T, F: Fn() -> T>(name: &str, input: &str, run: F) {
+    // warm up
+    run();
+
+    let mut total = Duration::default();
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        std::hint::black_box(run());
+        total += start.elapsed();
+    }
+
+    let average = total / ITERATIONS;
+    let megabytes = input.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<10} {:>8.2} ms/iter {:>8.2} MB/s",
+        name,
+        average.as_secs_f64() * 1000.0,
+        megabytes / average.as_secs_f64(),
+    );
+}
@@ -0,0 +1,564 @@
+use crate::parser::{CssParser};
+use crate::lexer::Token;
+use crate::error::ParserError;
+use crate::property_parser::PropertyParser;
+use crate::style_sheet::{AtRule, AtRuleBlock, AtRuleKind, Keyframe};
+use crate::span::Span;
+
+// A prelude token along with its source text
+type PreludeToken<'i> = (Token<'i>, &'i str);
+
+// At-rule Grammar:
+// at_rule := @name prelude ;
+//          | @name prelude { block }
+// The block is a rule list, declaration list or keyframe list depending on the name
+pub struct AtRuleParser<'a, 'i> {
+    css_parser: &'a mut CssParser<'i>,
+}
+
+impl<'a, 'i> AtRuleParser<'a, 'i> {
+    pub fn new(css_parser: &'a mut CssParser<'i>) -> AtRuleParser<'a, 'i> {
+        AtRuleParser { css_parser }
+    }
+
+    pub fn parse(&mut self) -> AtRule {
+        self.parse_at_rule()
+    }
+
+    fn parse_at_rule(&mut self) -> AtRule {
+        let start = self.css_parser.current_span();
+        let name = match self.css_parser.current_token(true) {
+            Token::AtKeyword(name) => name.into_owned(),
+            t => unreachable!("at-rules start with an at-keyword, found {}", t),
+        };
+        self.css_parser.try_next_token(false);
+
+        let prelude = self.parse_prelude();
+        let lowercase_name = name.to_ascii_lowercase();
+        let kind = match lowercase_name.as_str() {
+            "media" => AtRuleKind::Media { queries: split_list(&prelude) },
+            "import" => self.parse_import(&prelude),
+            "font-face" => AtRuleKind::FontFace,
+            "keyframes" | "-webkit-keyframes" | "-moz-keyframes" => self.parse_keyframes_name(&prelude),
+            "supports" => AtRuleKind::Supports { condition: join(&prelude) },
+            "page" => AtRuleKind::Page {
+                selector: Some(join(&prelude)).filter(|s| !s.is_empty()),
+            },
+            "namespace" => self.parse_namespace(&prelude),
+            "layer" => AtRuleKind::Layer { names: split_list(&prelude) },
+            _ => AtRuleKind::Unknown,
+        };
+
+        let next = self.css_parser.current_token(false);
+        let found = next.to_string();
+        let block = match next {
+            Token::LeftBrace(..) => Some(self.parse_block(&kind)),
+            Token::Semicolon(..) => {
+                self.css_parser.try_next_token(false);
+                None
+            },
+            // a missing `;` at the end of the style sheet or of a parent block is allowed
+            _ => None,
+        };
+
+        let has_block = block.is_some();
+        let requires_block = match kind {
+            AtRuleKind::Media { .. }
+            | AtRuleKind::FontFace
+            | AtRuleKind::Keyframes { .. }
+            | AtRuleKind::Supports { .. }
+            | AtRuleKind::Page { .. } => Some(true),
+            AtRuleKind::Import { .. } | AtRuleKind::Namespace { .. } => Some(false),
+            AtRuleKind::Layer { .. } | AtRuleKind::Unknown => None,
+        };
+
+        let span = start.to(self.css_parser.previous_span());
+        match requires_block {
+            Some(true) if !has_block => self.flag(&name, &found, Some("{"), span),
+            Some(false) if has_block => self.flag(&name, &found, Some(";"), span),
+            _ => (),
+        }
+
+        AtRule {
+            name,
+            prelude: join(&prelude),
+            block,
+            kind,
+            span,
+        }
+    }
+
+    fn parse_prelude(&mut self) -> Vec<PreludeToken<'i>> {
+        let mut prelude = vec![];
+        let mut depth = 0;
+
+        loop {
+            let token = self.css_parser.current_token(false);
+            match token {
+                Token::EOF => break,
+                Token::Semicolon(..) | Token::LeftBrace(..) | Token::RightBrace(..) if depth == 0 => break,
+                Token::Function(..) | Token::LeftParen(..) | Token::LeftBracket(..) => depth += 1,
+                Token::RightParen(..) | Token::RightBracket(..) if depth > 0 => depth -= 1,
+                _ => (),
+            }
+
+            let text = self.css_parser.current_text();
+            prelude.push((token, text));
+            self.css_parser.try_next_token(false);
+        }
+
+        prelude
+    }
+
+    fn parse_block(&mut self, kind: &AtRuleKind) -> AtRuleBlock {
+        match kind {
+            AtRuleKind::Media { .. } | AtRuleKind::Supports { .. } | AtRuleKind::Layer { .. } => {
+                self.css_parser.try_next_token(true);
+                let rules = self.css_parser.parse_rule_list(false);
+                self.expect_block_end();
+                AtRuleBlock::Rules(rules)
+            },
+            AtRuleKind::FontFace | AtRuleKind::Page { .. } => {
+                AtRuleBlock::Declarations(PropertyParser::new(self.css_parser).parse())
+            },
+            AtRuleKind::Keyframes { .. } => AtRuleBlock::Keyframes(self.parse_keyframes()),
+            _ => AtRuleBlock::Raw(self.parse_raw_block()),
+        }
+    }
+
+    fn expect_block_end(&mut self) {
+        match self.css_parser.current_token(true) {
+            Token::RightBrace(..) => {
+                self.css_parser.try_next_token(false);
+            },
+            t => {
+                let span = self.css_parser.current_span();
+                self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
+                    found: t.to_string(),
+                    expected: Some("}".to_string()),
+                    context: Some("at-rule".to_string()),
+                    span,
+                });
+            },
+        }
+    }
+
+    // keeps the source text between the braces
+    fn parse_raw_block(&mut self) -> String {
+        let mut text = String::new();
+        let mut depth = 0;
+
+        // consume `{`
+        self.css_parser.try_next_token(false);
+        loop {
+            match self.css_parser.current_token(false) {
+                Token::EOF => {
+                    self.expect_block_end();
+                    break;
+                },
+                Token::RightBrace(..) if depth == 0 => {
+                    self.css_parser.try_next_token(false);
+                    break;
+                },
+                Token::LeftBrace(..) => depth += 1,
+                Token::RightBrace(..) => depth -= 1,
+                _ => (),
+            }
+
+            text.push_str(self.css_parser.current_text());
+            self.css_parser.try_next_token(false);
+        }
+
+        text.trim().to_string()
+    }
+
+    fn parse_keyframes(&mut self) -> Vec<Keyframe> {
+        let mut keyframes = vec![];
+
+        // consume `{`
+        self.css_parser.try_next_token(true);
+        loop {
+            match self.css_parser.current_token(true) {
+                Token::RightBrace(..) | Token::EOF => break,
+                _ => {
+                    if let Some(keyframe) = self.parse_keyframe() {
+                        keyframes.push(keyframe);
+                    }
+                },
+            }
+        }
+
+        self.expect_block_end();
+        keyframes
+    }
+
+    // A keyframe with an invalid selector is dropped along with its block
+    fn parse_keyframe(&mut self) -> Option<Keyframe> {
+        let start = self.css_parser.current_span();
+        let mut percentages = vec![];
+        let mut invalid = false;
+
+        loop {
+            match self.css_parser.current_token(true) {
+                Token::LeftBrace(..) | Token::RightBrace(..) | Token::EOF => break,
+                Token::Identifier(name) if name.eq_ignore_ascii_case("from") => percentages.push(0.0),
+                Token::Identifier(name) if name.eq_ignore_ascii_case("to") => percentages.push(100.0),
+                Token::Percentage(number) if (0.0..=100.0).contains(&number.value) => {
+                    percentages.push(number.value as f32)
+                },
+                Token::Comma(..) => (),
+                t => {
+                    invalid = true;
+                    let span = self.css_parser.current_span();
+                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
+                        found: t.to_string(),
+                        expected: Some("keyframe selector".to_string()),
+                        context: Some("@keyframes".to_string()),
+                        span,
+                    });
+                    self.css_parser.skip_component_value();
+                    continue;
+                },
+            }
+
+            self.css_parser.try_next_token(true);
+        }
+
+        if invalid {
+            if let Token::LeftBrace(..) = self.css_parser.current_token(true) {
+                self.css_parser.skip_component_value();
+            }
+            return None;
+        }
+
+        let properties = match self.css_parser.current_token(true) {
+            Token::LeftBrace(..) => PropertyParser::new(self.css_parser).parse(),
+            _ => vec![],
+        };
+
+        Some(Keyframe {
+            percentages,
+            properties,
+            span: start.to(self.css_parser.previous_span()),
+        })
+    }
+
+    // @import url("x.css") layer(name) supports(condition) media-query-list;
+    fn parse_import(&mut self, prelude: &[PreludeToken<'i>]) -> AtRuleKind {
+        let mut rest = trim_whitespace(prelude);
+
+        let url = match take_url(&mut rest) {
+            Some(url) => url,
+            None => {
+                let span = self.css_parser.previous_span();
+                self.flag("import", &join(prelude), Some("url or string"), span);
+                return AtRuleKind::Unknown;
+            },
+        };
+
+        rest = trim_whitespace(rest);
+        let layer = match rest.first() {
+            Some((Token::Identifier(name), _)) if name.eq_ignore_ascii_case("layer") => {
+                rest = &rest[1..];
+                Some(String::new())
+            },
+            Some((Token::Function(name), _)) if name.eq_ignore_ascii_case("layer") => {
+                let (arguments, remaining) = take_function(rest);
+                rest = remaining;
+                Some(join(arguments))
+            },
+            _ => None,
+        };
+
+        rest = trim_whitespace(rest);
+        let supports = match rest.first() {
+            Some((Token::Function(name), _)) if name.eq_ignore_ascii_case("supports") => {
+                let (arguments, remaining) = take_function(rest);
+                rest = remaining;
+                Some(join(arguments))
+            },
+            _ => None,
+        };
+
+        AtRuleKind::Import {
+            url,
+            layer,
+            supports,
+            media: split_list(rest),
+        }
+    }
+
+    // @namespace prefix? url;
+    fn parse_namespace(&mut self, prelude: &[PreludeToken<'i>]) -> AtRuleKind {
+        let mut rest = trim_whitespace(prelude);
+
+        let prefix = match rest.first() {
+            Some((Token::Identifier(prefix), _)) => {
+                let prefix = prefix.to_string();
+                rest = trim_whitespace(&rest[1..]);
+                Some(prefix)
+            },
+            _ => None,
+        };
+
+        match take_url(&mut rest) {
+            Some(_) if !self.css_parser.namespaces_allowed => {
+                let span = self.css_parser.previous_span();
+                self.css_parser.error_handler.flag(&ParserError::MisplacedAtRule("namespace".to_string(), span));
+                AtRuleKind::Unknown
+            },
+            Some(url) if trim_whitespace(rest).is_empty() => {
+                let key = prefix.clone().unwrap_or_default();
+                self.css_parser.namespaces.insert(key, url.clone());
+                AtRuleKind::Namespace { prefix, url }
+            },
+            _ => {
+                let span = self.css_parser.previous_span();
+                self.flag("namespace", &join(prelude), Some("url or string"), span);
+                AtRuleKind::Unknown
+            },
+        }
+    }
+
+    fn parse_keyframes_name(&mut self, prelude: &[PreludeToken<'i>]) -> AtRuleKind {
+        match trim_whitespace(prelude) {
+            [(Token::Identifier(name), _)] | [(Token::String(name), _)] => AtRuleKind::Keyframes {
+                name: name.to_string(),
+            },
+            _ => {
+                let span = self.css_parser.previous_span();
+                self.flag("keyframes", &join(prelude), Some("name"), span);
+                AtRuleKind::Unknown
+            },
+        }
+    }
+
+    fn flag(&mut self, name: &str, found: &str, expected: Option<&str>, span: Span) {
+        self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
+            found: found.to_string(),
+            expected: expected.map(|e| e.to_string()),
+            context: Some(format!("@{}", name)),
+            span,
+        });
+    }
+}
+
+// joins the source text of the tokens, collapsing whitespace into a single space
+fn join(tokens: &[PreludeToken]) -> String {
+    let mut value = String::new();
+    for (token, text) in trim_whitespace(tokens) {
+        match token {
+            Token::Whitespace(..) => value.push(' '),
+            _ => value.push_str(text),
+        }
+    }
+
+    value
+}
+
+// splits a comma separated list, commas nested in functions or blocks are ignored
+fn split_list(tokens: &[PreludeToken]) -> Vec<String> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (index, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Function(..) | Token::LeftParen(..) | Token::LeftBracket(..) => depth += 1,
+            Token::RightParen(..) | Token::RightBracket(..) if depth > 0 => depth -= 1,
+            Token::Comma(..) if depth == 0 => {
+                items.push(join(&tokens[start..index]));
+                start = index + 1;
+            },
+            _ => (),
+        }
+    }
+
+    let last = join(&tokens[start..]);
+    if !last.is_empty() || !items.is_empty() {
+        items.push(last);
+    }
+
+    items
+}
+
+fn trim_whitespace<'t, 'i>(tokens: &'t [PreludeToken<'i>]) -> &'t [PreludeToken<'i>] {
+    let is_whitespace = |(token, _): &PreludeToken| matches!(token, Token::Whitespace(..));
+    let start = tokens.iter().position(|t| !is_whitespace(t)).unwrap_or(tokens.len());
+    let end = tokens.iter().rposition(|t| !is_whitespace(t)).map_or(start, |end| end + 1);
+
+    &tokens[start..end]
+}
+
+// takes a leading `url(x)`, `url("x")` or `"x"`
+fn take_url(tokens: &mut &[PreludeToken]) -> Option<String> {
+    let (url, length) = match *tokens {
+        [(Token::Url(url), _), ..] | [(Token::String(url), _), ..] => (url.to_string(), 1),
+        [(Token::Function(name), _), ..] if name.eq_ignore_ascii_case("url") => {
+            let (arguments, remaining) = take_function(tokens);
+            let url = match trim_whitespace(arguments) {
+                [(Token::String(url), _)] => url.to_string(),
+                _ => return None,
+            };
+            (url, tokens.len() - remaining.len())
+        },
+        _ => return None,
+    };
+
+    *tokens = &tokens[length..];
+    Some(url)
+}
+
+// splits a leading function into its arguments and the tokens after its `)`
+fn take_function<'t, 'i>(tokens: &'t [PreludeToken<'i>]) -> (&'t [PreludeToken<'i>], &'t [PreludeToken<'i>]) {
+    let mut depth = 0;
+    for (index, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Function(..) | Token::LeftParen(..) | Token::LeftBracket(..) => depth += 1,
+            Token::RightParen(..) | Token::RightBracket(..) => {
+                depth -= 1;
+                if depth == 0 {
+                    return (&tokens[1..index], &tokens[index + 1..]);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    // an unclosed function runs to the end of the prelude
+    (&tokens[1..], &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParserError;
+    use crate::style_sheet::{CssRule, AtRule, AtRuleBlock, AtRuleKind};
+
+    fn at_rules(css: &str) -> Vec<AtRule> {
+        let output = crate::parse_str(css);
+        assert!(output.errors.is_empty(), "{}: {:?}", css, output.errors);
+        output.stylesheet.rules.into_iter().filter_map(|rule| match rule {
+            CssRule::At(rule) => Some(rule),
+            _ => None,
+        }).collect()
+    }
+
+    fn kinds(css: &str) -> Vec<&'static str> {
+        crate::parse_str(css).stylesheet.rules.iter().map(|rule| match rule {
+            CssRule::At(rule) => match rule.kind {
+                AtRuleKind::Namespace { .. } => "namespace",
+                AtRuleKind::Import { .. } => "import",
+                AtRuleKind::Unknown => "unknown",
+                _ => "other",
+            },
+            CssRule::Style(..) => "style",
+        }).collect()
+    }
+
+    #[test]
+    fn rejects_namespaces_after_other_rules() {
+        let css = "@charset \"utf-8\"; @import 'a.css'; @namespace svg url(x); @namespace url(y);";
+        assert!(crate::parse_str(css).errors.is_empty());
+
+        let css = "@namespace svg url(x); a {} @namespace url(y); @media print { @namespace url(z); }";
+        let output = crate::parse_str(css);
+        assert_eq!(kinds(css), ["namespace", "style", "unknown", "other"]);
+        assert_eq!(output.errors.len(), 2);
+        assert!(output.errors.iter().all(|error| matches!(error, ParserError::MisplacedAtRule(name, _) if name == "namespace")));
+
+        // the rejected default namespace doesn't apply to later selectors
+        let output = crate::parse_str("b {} @namespace svg url(x); svg|a {}");
+        assert!(output.errors.iter().any(|error| matches!(error, ParserError::UndeclaredNamespace(..))));
+    }
+
+    #[test]
+    fn flags_invalid_keyframe_selectors() {
+        let css = "@keyframes spin { from {} 50%, 75% {} to {} 120% {} -1% {} 10px {} middle {} 0%, 101% {} }";
+        let output = crate::parse_str(css);
+        let percentages = match output.stylesheet.rules.first() {
+            Some(CssRule::At(rule)) => match &rule.block {
+                Some(AtRuleBlock::Keyframes(keyframes)) => {
+                    keyframes.iter().map(|keyframe| keyframe.percentages.clone()).collect::<Vec<_>>()
+                },
+                block => panic!("unexpected block {:?}", block),
+            },
+            rule => panic!("unexpected rule {:?}", rule),
+        };
+        assert_eq!(percentages, [vec![0.0], vec![50.0, 75.0], vec![100.0]]);
+        assert_eq!(output.errors.len(), 5);
+    }
+
+    #[test]
+    fn parses_imports() {
+        let rules = at_rules(
+            "@import url(\"a.css\") layer(base.reset) supports(display: grid) screen and (min-width: 1px), print; \
+            @import 'b.css' layer; @import url(c.css);",
+        );
+        assert!(matches!(
+            &rules[0].kind,
+            AtRuleKind::Import { url, layer: Some(layer), supports: Some(supports), media }
+                if url == "a.css" && layer == "base.reset" && supports == "display: grid"
+                && media == &["screen and (min-width: 1px)", "print"]
+        ));
+        assert!(matches!(&rules[1].kind, AtRuleKind::Import { url, layer: Some(layer), supports: None, media }
+            if url == "b.css" && layer.is_empty() && media.is_empty()));
+        assert!(matches!(&rules[2].kind, AtRuleKind::Import { url, layer: None, .. } if url == "c.css"));
+        assert!(rules.iter().all(|rule| rule.block.is_none()));
+    }
+
+    #[test]
+    fn parses_namespaces() {
+        let rules = at_rules("@namespace svg url(http://www.w3.org/2000/svg); @NAMESPACE \"http://x\";");
+        assert!(matches!(&rules[0].kind, AtRuleKind::Namespace { prefix: Some(prefix), url }
+            if prefix == "svg" && url == "http://www.w3.org/2000/svg"));
+        assert!(matches!(&rules[1].kind, AtRuleKind::Namespace { prefix: None, url } if url == "http://x"));
+        assert_eq!(rules[1].name, "NAMESPACE");
+    }
+
+    #[test]
+    fn parses_keyframes_and_font_faces() {
+        let rules = at_rules("@keyframes \"spin\" { from { opacity: 0 } 50% {} to {} } @font-face { font-family: x; src: url(x.woff) }");
+        assert!(matches!(&rules[0].kind, AtRuleKind::Keyframes { name } if name == "spin"));
+        match &rules[0].block {
+            Some(AtRuleBlock::Keyframes(keyframes)) => {
+                assert_eq!(keyframes.iter().map(|keyframe| keyframe.percentages[0]).collect::<Vec<_>>(), [0.0, 50.0, 100.0]);
+                assert_eq!(keyframes[0].properties[0].name, "opacity");
+            },
+            block => panic!("unexpected block {:?}", block),
+        }
+
+        assert!(matches!(rules[1].kind, AtRuleKind::FontFace));
+        match &rules[1].block {
+            Some(AtRuleBlock::Declarations(properties)) => assert_eq!(properties.len(), 2),
+            block => panic!("unexpected block {:?}", block),
+        }
+    }
+
+    #[test]
+    fn parses_nested_rule_lists() {
+        let rules = at_rules("@media screen, print { a {} @supports (display: grid) { b {} } } @layer a.b, c; @layer { d {} }");
+        assert!(matches!(&rules[0].kind, AtRuleKind::Media { queries } if queries == &["screen", "print"]));
+        match &rules[0].block {
+            Some(AtRuleBlock::Rules(nested)) => {
+                assert!(matches!(nested[0], CssRule::Style(..)));
+                assert!(matches!(&nested[1], CssRule::At(rule)
+                    if matches!(&rule.kind, AtRuleKind::Supports { condition } if condition == "(display: grid)")));
+            },
+            block => panic!("unexpected block {:?}", block),
+        }
+        assert!(matches!(&rules[1].kind, AtRuleKind::Layer { names } if names == &["a.b", "c"]));
+        assert!(matches!(&rules[2].kind, AtRuleKind::Layer { names } if names.is_empty()));
+    }
+
+    #[test]
+    fn flags_malformed_at_rules() {
+        for css in ["@import 1;", "@keyframes 1 {}", "@namespace a b;", "@media print;", "@import 'a.css' {}"] {
+            let output = crate::parse_str(css);
+            assert_eq!(output.errors.len(), 1, "{}: {:?}", css, output.errors);
+        }
+        // unknown at-rules keep their block as text
+        let rules = at_rules("@unknown foo { bar { baz } }");
+        assert!(matches!(rules[0].kind, AtRuleKind::Unknown));
+        assert!(matches!(&rules[0].block, Some(AtRuleBlock::Raw(text)) if text == "bar { baz }"));
+    }
+}
@@ -0,0 +1,547 @@
+use crate::style_sheet::{
+    CalcNode, CalcType, BaseType, MathFunction, RoundingStrategy, CalcContext, LengthUnit, AngleUnit, TimeUnit,
+    FrequencyUnit, ResolutionUnit,
+};
+
+// Type checking per CSS Values 4, `None` for an invalid calculation
+pub(crate) fn calc_type(node: &CalcNode) -> Option<CalcType> {
+    match node {
+        CalcNode::Number(..) => Some(CalcType::number()),
+        CalcNode::Percentage(..) => Some(CalcType::of(BaseType::Percent)),
+        CalcNode::Length(..) => Some(CalcType::of(BaseType::Length)),
+        CalcNode::Angle(..) => Some(CalcType::of(BaseType::Angle)),
+        CalcNode::Time(..) => Some(CalcType::of(BaseType::Time)),
+        CalcNode::Frequency(..) => Some(CalcType::of(BaseType::Frequency)),
+        CalcNode::Resolution(..) => Some(CalcType::of(BaseType::Resolution)),
+        CalcNode::Sum(children) => fold_types(children, add_types),
+        CalcNode::Product(children) => fold_types(children, multiply_types),
+        CalcNode::Negate(child) => calc_type(child),
+        CalcNode::Invert(child) => calc_type(child).map(invert_type),
+        CalcNode::Function(function, arguments) => function_type(*function, arguments),
+    }
+}
+
+fn fold_types(nodes: &[CalcNode], combine: fn(CalcType, CalcType) -> Option<CalcType>) -> Option<CalcType> {
+    let (first, rest) = nodes.split_first()?;
+    rest.iter().try_fold(calc_type(first)?, |result, node| combine(result, calc_type(node)?))
+}
+
+fn add_types(first: CalcType, second: CalcType) -> Option<CalcType> {
+    let (first, second) = match (first.percent_hint, second.percent_hint) {
+        (Some(hint), None) => (first, apply_percent_hint(second, hint)?),
+        (None, Some(hint)) => (apply_percent_hint(first, hint)?, second),
+        (Some(first_hint), Some(second_hint)) if first_hint != second_hint => return None,
+        _ => (first, second),
+    };
+    if first == second {
+        return Some(first);
+    }
+
+    // percentages that meet another type resolve against it, eg `100% - 2px`
+    let has_percent = |calc_type: CalcType| calc_type.exponents[BaseType::Percent as usize] != 0;
+    if has_percent(first) || has_percent(second) {
+        for &base_type in &BaseType::ALL[..5] {
+            let first = apply_percent_hint(first, base_type)?;
+            let second = apply_percent_hint(second, base_type)?;
+            if first == second {
+                return Some(first);
+            }
+        }
+    }
+    None
+}
+
+fn multiply_types(first: CalcType, second: CalcType) -> Option<CalcType> {
+    let (first, second) = match (first.percent_hint, second.percent_hint) {
+        (Some(hint), None) => (first, apply_percent_hint(second, hint)?),
+        (None, Some(hint)) => (apply_percent_hint(first, hint)?, second),
+        (Some(first_hint), Some(second_hint)) if first_hint != second_hint => return None,
+        _ => (first, second),
+    };
+
+    let mut exponents = first.exponents;
+    for (exponent, other) in exponents.iter_mut().zip(second.exponents.iter()) {
+        *exponent = exponent.checked_add(*other)?;
+    }
+    Some(CalcType { exponents, percent_hint: first.percent_hint.or(second.percent_hint) })
+}
+
+fn invert_type(calc_type: CalcType) -> CalcType {
+    let mut exponents = calc_type.exponents;
+    for exponent in exponents.iter_mut() {
+        *exponent = -*exponent;
+    }
+    CalcType { exponents, ..calc_type }
+}
+
+// moves the percent exponent over to `hint`
+fn apply_percent_hint(calc_type: CalcType, hint: BaseType) -> Option<CalcType> {
+    let mut exponents = calc_type.exponents;
+    let percent = std::mem::take(&mut exponents[BaseType::Percent as usize]);
+    exponents[hint as usize] = exponents[hint as usize].checked_add(percent)?;
+    Some(CalcType { exponents, percent_hint: Some(hint) })
+}
+
+fn function_type(function: MathFunction, arguments: &[CalcNode]) -> Option<CalcType> {
+    let types = arguments.iter().map(calc_type).collect::<Option<Vec<_>>>()?;
+    let (&first, rest) = types.split_first()?;
+    let added = rest.iter().try_fold(first, |result, &calc_type| add_types(result, calc_type))?;
+    let numbers = types.iter().all(CalcType::is_number);
+
+    match function {
+        // a step can only be left out when rounding a number
+        MathFunction::Round(..) if types.len() == 1 && !numbers => None,
+        MathFunction::Min | MathFunction::Max | MathFunction::Clamp | MathFunction::Round(..) | MathFunction::Mod
+        | MathFunction::Rem | MathFunction::Hypot | MathFunction::Abs => Some(added),
+        MathFunction::Sign => Some(CalcType::number()),
+        MathFunction::Sin | MathFunction::Cos | MathFunction::Tan => {
+            (numbers || added.base_type() == Some(BaseType::Angle)).then(CalcType::number)
+        },
+        MathFunction::Asin | MathFunction::Acos | MathFunction::Atan => {
+            numbers.then(|| CalcType::of(BaseType::Angle))
+        },
+        MathFunction::Atan2 => Some(CalcType::of(BaseType::Angle)),
+        MathFunction::Pow | MathFunction::Sqrt | MathFunction::Log | MathFunction::Exp => {
+            numbers.then(CalcType::number)
+        },
+    }
+}
+
+// The unit of a leaf, leaves with the same one can be combined
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LeafUnit {
+    Number,
+    Percentage,
+    Length(LengthUnit),
+    Angle(AngleUnit),
+    Time(TimeUnit),
+    Frequency(FrequencyUnit),
+    Resolution(ResolutionUnit),
+}
+
+impl LeafUnit {
+    // px, deg, s, hz and dppx
+    fn is_canonical(self) -> bool {
+        matches!(
+            self,
+            LeafUnit::Length(LengthUnit::Px) | LeafUnit::Angle(AngleUnit::Deg) | LeafUnit::Time(TimeUnit::S)
+            | LeafUnit::Frequency(FrequencyUnit::Hz) | LeafUnit::Resolution(ResolutionUnit::Dppx)
+        )
+    }
+
+    fn canonical(base_type: BaseType) -> LeafUnit {
+        match base_type {
+            BaseType::Length => LeafUnit::Length(LengthUnit::Px),
+            BaseType::Angle => LeafUnit::Angle(AngleUnit::Deg),
+            BaseType::Time => LeafUnit::Time(TimeUnit::S),
+            BaseType::Frequency => LeafUnit::Frequency(FrequencyUnit::Hz),
+            BaseType::Resolution => LeafUnit::Resolution(ResolutionUnit::Dppx),
+            BaseType::Percent => LeafUnit::Percentage,
+        }
+    }
+}
+
+fn leaf_parts(node: &CalcNode) -> Option<(f32, LeafUnit)> {
+    match *node {
+        CalcNode::Number(value) => Some((value, LeafUnit::Number)),
+        CalcNode::Percentage(value) => Some((value, LeafUnit::Percentage)),
+        CalcNode::Length(value, unit) => Some((value, LeafUnit::Length(unit))),
+        CalcNode::Angle(value, unit) => Some((value, LeafUnit::Angle(unit))),
+        CalcNode::Time(value, unit) => Some((value, LeafUnit::Time(unit))),
+        CalcNode::Frequency(value, unit) => Some((value, LeafUnit::Frequency(unit))),
+        CalcNode::Resolution(value, unit) => Some((value, LeafUnit::Resolution(unit))),
+        _ => None,
+    }
+}
+
+fn make_leaf(value: f32, unit: LeafUnit) -> CalcNode {
+    match unit {
+        LeafUnit::Number => CalcNode::Number(value),
+        LeafUnit::Percentage => CalcNode::Percentage(value),
+        LeafUnit::Length(unit) => CalcNode::Length(value, unit),
+        LeafUnit::Angle(unit) => CalcNode::Angle(value, unit),
+        LeafUnit::Time(unit) => CalcNode::Time(value, unit),
+        LeafUnit::Frequency(unit) => CalcNode::Frequency(value, unit),
+        LeafUnit::Resolution(unit) => CalcNode::Resolution(value, unit),
+    }
+}
+
+// absolute units are converted to the canonical one of their type, relative ones are kept
+fn canonicalize(node: CalcNode) -> CalcNode {
+    match node {
+        CalcNode::Length(value, unit) => match unit.to_px() {
+            Some(px) => CalcNode::Length(value * px, LengthUnit::Px),
+            None => node,
+        },
+        CalcNode::Angle(value, unit) => CalcNode::Angle(value * unit.to_degrees(), AngleUnit::Deg),
+        CalcNode::Time(value, unit) => CalcNode::Time(value * unit.to_seconds(), TimeUnit::S),
+        CalcNode::Frequency(value, unit) => CalcNode::Frequency(value * unit.to_hertz(), FrequencyUnit::Hz),
+        CalcNode::Resolution(value, unit) => CalcNode::Resolution(value * unit.to_dppx(), ResolutionUnit::Dppx),
+        node => node,
+    }
+}
+
+pub(crate) fn simplify(node: CalcNode) -> CalcNode {
+    match node {
+        CalcNode::Sum(children) => simplify_sum(children),
+        CalcNode::Product(children) => simplify_product(children),
+        CalcNode::Negate(child) => match simplify(*child) {
+            CalcNode::Negate(inner) => *inner,
+            child => match leaf_parts(&child) {
+                Some((value, unit)) => make_leaf(-value, unit),
+                None => CalcNode::Negate(Box::new(child)),
+            },
+        },
+        CalcNode::Invert(child) => match simplify(*child) {
+            CalcNode::Invert(inner) => *inner,
+            CalcNode::Number(value) => CalcNode::Number(1.0 / value),
+            child => CalcNode::Invert(Box::new(child)),
+        },
+        CalcNode::Function(function, arguments) => {
+            simplify_function(function, arguments.into_iter().map(simplify).collect())
+        },
+        leaf => canonicalize(leaf),
+    }
+}
+
+fn simplify_sum(children: Vec<CalcNode>) -> CalcNode {
+    let mut terms: Vec<CalcNode> = vec![];
+    for child in children.into_iter().map(simplify) {
+        let flattened = match child {
+            CalcNode::Sum(inner) => inner,
+            child => vec![child],
+        };
+
+        for term in flattened {
+            // leaves of the same unit are added together
+            if let Some((value, unit)) = leaf_parts(&term) {
+                let existing = terms.iter().position(|existing| leaf_parts(existing).is_some_and(|(_, other)| other == unit));
+                if let Some(index) = existing {
+                    let (existing_value, _) = leaf_parts(&terms[index]).unwrap_or((0.0, unit));
+                    terms[index] = make_leaf(existing_value + value, unit);
+                    continue;
+                }
+            }
+            terms.push(term);
+        }
+    }
+
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        CalcNode::Sum(terms)
+    }
+}
+
+fn simplify_product(children: Vec<CalcNode>) -> CalcNode {
+    let mut number = 1.0;
+    let mut factors = vec![];
+    for child in children.into_iter().map(simplify) {
+        let flattened = match child {
+            CalcNode::Product(inner) => inner,
+            child => vec![child],
+        };
+
+        for factor in flattened {
+            match factor {
+                CalcNode::Number(value) => number *= value,
+                factor => factors.push(factor),
+            }
+        }
+    }
+
+    match factors.as_slice() {
+        [] => return CalcNode::Number(number),
+        // a single leaf or a sum of leaves is scaled by the number, eg `2 * (1px + 1em)`
+        [factor] => {
+            if let Some((value, unit)) = leaf_parts(factor) {
+                return make_leaf(value * number, unit);
+            }
+            if let CalcNode::Sum(terms) = factor {
+                if terms.iter().all(CalcNode::is_leaf) {
+                    return CalcNode::Sum(terms.iter().filter_map(leaf_parts).map(|(value, unit)| make_leaf(value * number, unit)).collect());
+                }
+            }
+        },
+        _ => {
+            if let Some(leaf) = multiply_leaves(number, &factors) {
+                return leaf;
+            }
+        },
+    }
+
+    if number != 1.0 {
+        factors.insert(0, CalcNode::Number(number));
+    }
+    if factors.len() == 1 {
+        factors.remove(0)
+    } else {
+        CalcNode::Product(factors)
+    }
+}
+
+// Leaves and inverted leaves that share a unit, or all have a canonical one, multiply into a
+// single leaf as long as the result is a number or a single type, eg `2px * 3px / 1px`
+fn multiply_leaves(number: f32, factors: &[CalcNode]) -> Option<CalcNode> {
+    let mut value = number;
+    let mut units = vec![];
+    for factor in factors {
+        match factor {
+            CalcNode::Invert(child) => {
+                let (child_value, unit) = leaf_parts(child)?;
+                value /= child_value;
+                units.push(unit);
+            },
+            factor => {
+                let (factor_value, unit) = leaf_parts(factor)?;
+                value *= factor_value;
+                units.push(unit);
+            },
+        }
+    }
+
+    let same_unit = units.windows(2).all(|pair| pair[0] == pair[1]);
+    if !same_unit && !units.iter().all(|unit| unit.is_canonical()) {
+        return None;
+    }
+
+    let calc_type = calc_type(&CalcNode::Product(factors.to_vec()))?;
+    if calc_type.is_number() {
+        return Some(CalcNode::Number(value));
+    }
+    match calc_type.base_type() {
+        Some(_) if same_unit => Some(make_leaf(value, units[0])),
+        Some(base_type) if calc_type.percent_hint.is_none() => Some(make_leaf(value, LeafUnit::canonical(base_type))),
+        _ => None,
+    }
+}
+
+// functions of leaves that share a unit are computed right away
+fn simplify_function(function: MathFunction, arguments: Vec<CalcNode>) -> CalcNode {
+    let leaves = arguments.iter().map(leaf_parts).collect::<Option<Vec<_>>>();
+    let leaves = match leaves {
+        Some(leaves) if leaves.windows(2).all(|pair| pair[0].1 == pair[1].1) => leaves,
+        _ => return CalcNode::Function(function, arguments),
+    };
+
+    let unit = leaves[0].1;
+    let values = leaves.iter().map(|&(value, _)| value as f64).collect::<Vec<_>>();
+    // angles are canonical by now, so in degrees
+    let value = apply(function, &values, matches!(unit, LeafUnit::Angle(..))) as f32;
+    match function {
+        MathFunction::Sign | MathFunction::Sin | MathFunction::Cos | MathFunction::Tan | MathFunction::Pow
+        | MathFunction::Sqrt | MathFunction::Log | MathFunction::Exp => CalcNode::Number(value),
+        MathFunction::Asin | MathFunction::Acos | MathFunction::Atan | MathFunction::Atan2 => {
+            CalcNode::Angle(value, AngleUnit::Deg)
+        },
+        _ => make_leaf(value, unit),
+    }
+}
+
+pub(crate) fn evaluate(node: &CalcNode, context: &CalcContext) -> Option<f32> {
+    // a calculation of nothing but percentages is fine without a basis, eg `opacity: calc(50% / 2)`
+    let raw_percentages = context.percentage_basis.is_none() && calc_type(node)? == CalcType::of(BaseType::Percent);
+    evaluate_node(node, context, raw_percentages).map(|value| value as f32)
+}
+
+fn evaluate_node(node: &CalcNode, context: &CalcContext, raw_percentages: bool) -> Option<f64> {
+    let evaluate = |node: &CalcNode| evaluate_node(node, context, raw_percentages);
+
+    Some(match *node {
+        CalcNode::Number(value) => value as f64,
+        CalcNode::Percentage(value) if raw_percentages => value as f64,
+        CalcNode::Percentage(value) => context.percentage_basis? as f64 * value as f64 / 100.0,
+        CalcNode::Length(value, unit) => context.length_to_px(value, unit) as f64,
+        CalcNode::Angle(value, unit) => value as f64 * unit.to_degrees() as f64,
+        CalcNode::Time(value, unit) => value as f64 * unit.to_seconds() as f64,
+        CalcNode::Frequency(value, unit) => value as f64 * unit.to_hertz() as f64,
+        CalcNode::Resolution(value, unit) => value as f64 * unit.to_dppx() as f64,
+        CalcNode::Sum(ref children) => children.iter().map(evaluate).sum::<Option<f64>>()?,
+        CalcNode::Product(ref children) => children.iter().map(evaluate).product::<Option<f64>>()?,
+        CalcNode::Negate(ref child) => -evaluate(child)?,
+        CalcNode::Invert(ref child) => 1.0 / evaluate(child)?,
+        CalcNode::Function(function, ref arguments) => {
+            let values = arguments.iter().map(evaluate).collect::<Option<Vec<_>>>()?;
+            let angle = arguments.first().and_then(calc_type).and_then(|calc_type| calc_type.base_type()) == Some(BaseType::Angle);
+            apply(function, &values, angle)
+        },
+    })
+}
+
+// `angle` says whether the arguments are in degrees rather than plain numbers, which the
+// trigonometric functions take as radians
+fn apply(function: MathFunction, arguments: &[f64], angle: bool) -> f64 {
+    let first = arguments.first().copied().unwrap_or(f64::NAN);
+    let second = arguments.get(1).copied();
+    let radians = if angle { first.to_radians() } else { first };
+
+    match function {
+        MathFunction::Min => arguments.iter().copied().fold(f64::INFINITY, |result, value| {
+            if result.is_nan() || value.is_nan() { f64::NAN } else { result.min(value) }
+        }),
+        MathFunction::Max => arguments.iter().copied().fold(f64::NEG_INFINITY, |result, value| {
+            if result.is_nan() || value.is_nan() { f64::NAN } else { result.max(value) }
+        }),
+        // the minimum wins over the maximum
+        MathFunction::Clamp => {
+            let (value, max) = (second.unwrap_or(f64::NAN), arguments.get(2).copied().unwrap_or(f64::NAN));
+            if first.is_nan() || value.is_nan() || max.is_nan() {
+                f64::NAN
+            } else {
+                value.min(max).max(first)
+            }
+        },
+        MathFunction::Round(strategy) => round(strategy, first, second.unwrap_or(1.0)),
+        MathFunction::Mod => {
+            let step = second.unwrap_or(f64::NAN);
+            first - step * (first / step).floor()
+        },
+        MathFunction::Rem => {
+            let step = second.unwrap_or(f64::NAN);
+            first - step * (first / step).trunc()
+        },
+        MathFunction::Sin => radians.sin(),
+        MathFunction::Cos => radians.cos(),
+        MathFunction::Tan => radians.tan(),
+        MathFunction::Asin => first.asin().to_degrees(),
+        MathFunction::Acos => first.acos().to_degrees(),
+        MathFunction::Atan => first.atan().to_degrees(),
+        MathFunction::Atan2 => first.atan2(second.unwrap_or(f64::NAN)).to_degrees(),
+        MathFunction::Pow => first.powf(second.unwrap_or(f64::NAN)),
+        MathFunction::Sqrt => first.sqrt(),
+        MathFunction::Hypot => arguments.iter().map(|value| value * value).sum::<f64>().sqrt(),
+        MathFunction::Log => match second {
+            Some(base) => first.ln() / base.ln(),
+            None => first.ln(),
+        },
+        MathFunction::Exp => first.exp(),
+        MathFunction::Abs => first.abs(),
+        // zero keeps its sign
+        MathFunction::Sign => {
+            if first > 0.0 {
+                1.0
+            } else if first < 0.0 {
+                -1.0
+            } else {
+                first
+            }
+        },
+    }
+}
+
+fn round(strategy: RoundingStrategy, value: f64, step: f64) -> f64 {
+    if step == 0.0 {
+        return f64::NAN;
+    }
+    let step = step.abs();
+    let (down, up) = ((value / step).floor() * step, (value / step).ceil() * step);
+
+    match strategy {
+        RoundingStrategy::Nearest => if value - down < up - value { down } else { up },
+        RoundingStrategy::Up => up,
+        RoundingStrategy::Down => down,
+        RoundingStrategy::ToZero => if value < 0.0 { up } else { down },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::style_sheet::{CalcNode, CalcContext, CalcType, BaseType, LengthUnit};
+
+    fn parse(input: &str) -> Option<CalcNode> {
+        CalcNode::parse(&crate::parse_component_values(input))
+    }
+
+    fn simplified(input: &str) -> String {
+        parse(input).map_or_else(|| "invalid".to_string(), |node| node.to_string())
+    }
+
+    // 16px font, 1000x800 viewport, percentages of 200px
+    fn evaluate(input: &str) -> Option<f32> {
+        let context = CalcContext { percentage_basis: Some(200.0), ..CalcContext::new(16.0, 1000.0, 800.0) };
+        parse(input)?.evaluate(&context)
+    }
+
+    #[test]
+    fn rejects_type_mismatches() {
+        for input in [
+            "calc(1px + 2s)", "calc(1px + 1)", "calc(1px * 1px)", "calc(1 / 1px)", "min(1px, 1deg)",
+            "sin(1px)", "pow(2px, 2)", "sqrt(4px)", "round(7px)", "calc(1px -2px)", "calc(1px +2px)",
+            "calc(1foo)", "min()", "clamp(1px, 2px)", "mod(1px)", "calc()", "calc(1px, 2px)",
+        ] {
+            assert_eq!(simplified(input), "invalid", "{}", input);
+        }
+    }
+
+    #[test]
+    fn computes_types() {
+        let length = CalcType::of(BaseType::Length);
+        assert_eq!(parse("calc(1px + 1em)").and_then(|node| node.calc_type()), Some(length));
+        assert_eq!(parse("calc(10px / 2px)").and_then(|node| node.calc_type()), Some(CalcType::number()));
+        assert_eq!(
+            parse("calc(100% - 2rem)").and_then(|node| node.calc_type()),
+            Some(CalcType { percent_hint: Some(BaseType::Length), ..length }),
+        );
+        assert_eq!(parse("atan2(1px, 2px)").and_then(|node| node.calc_type()), Some(CalcType::of(BaseType::Angle)));
+    }
+
+    #[test]
+    fn simplifies_at_parse_time() {
+        let cases = [
+            ("calc(1in + 2px)", "98px"),
+            ("calc(1s + 500ms)", "1.5s"),
+            ("calc(2 * (1px + 1em))", "2px + 2em"),
+            ("calc((1px + 1em) / 2)", "0.5px + 0.5em"),
+            ("calc(100% - 2rem)", "100% - 2rem"),
+            ("calc(1em - 2px - 3px)", "1em - 5px"),
+            ("calc(10px / 2px)", "5"),
+            ("calc(2px * 3px / 1px)", "6px"),
+            ("min(1px, 2px)", "1px"),
+            ("max(1px, 1em, 2rem)", "max(1px, 1em, 2rem)"),
+            ("clamp(10px, 1em + 2vw, 50px)", "clamp(10px, 1em + 2vw, 50px)"),
+            ("round(up, 7px, 5px)", "10px"),
+            ("round(to-zero, -7.5, 2)", "-6"),
+            ("round(7.5)", "8"),
+            ("mod(-7, 3)", "2"),
+            ("rem(-7, 3)", "-1"),
+            ("sin(90deg)", "1"),
+            ("atan2(1, 1)", "45deg"),
+            ("pow(2, 3)", "8"),
+            ("hypot(3px, 4px)", "5px"),
+            ("log(8, 2)", "3"),
+            ("abs(calc(-2px))", "2px"),
+            ("sign(-3em)", "-1"),
+            ("calc(1 / 0)", "infinity"),
+            ("calc(1px * -infinity)", "-infinity * 1px"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(simplified(input), expected, "{}", input);
+        }
+        assert_eq!(parse("calc(1.5em * 2)"), Some(CalcNode::Length(3.0, LengthUnit::Em)));
+    }
+
+    #[test]
+    fn evaluates_against_the_context() {
+        let cases = [
+            ("calc(100% - 2rem)", 168.0),
+            ("calc(2em + 10vw)", 132.0),
+            ("clamp(10px, 1em + 2vw, 50px)", 36.0),
+            ("max(1px, 1em, 2rem)", 32.0),
+            ("min(50%, 10vh)", 80.0),
+            ("calc(1lh)", 19.2),
+            ("round(down, 1em + 3px, 5px)", 15.0),
+        ];
+        for (input, expected) in cases {
+            let value = evaluate(input).unwrap_or(f32::NAN);
+            assert!((value - expected).abs() < 0.001, "{} is {} rather than {}", input, value, expected);
+        }
+    }
+
+    #[test]
+    fn percentages_need_a_basis() {
+        let context = CalcContext::new(16.0, 1000.0, 800.0);
+        assert_eq!(parse("calc(100% - 2px)").and_then(|node| node.evaluate(&context)), None);
+        // nothing to resolve them against, they stay percentages
+        assert_eq!(parse("calc(50% / 2)").and_then(|node| node.evaluate(&context)), Some(25.0));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::style_sheet::{
+    ComponentValue, BlockKind, CalcNode, MathFunction, RoundingStrategy, LengthUnit, AngleUnit, TimeUnit, FrequencyUnit,
+    ResolutionUnit,
+};
+use crate::value_parser::trim_whitespace;
+
+// A math function making up a whole value, eg `calc(100% - 2rem)` or `min(1px, 2em)`
+pub fn parse_math_function(values: &[ComponentValue]) -> Option<CalcNode> {
+    let node = match trim_whitespace(values) {
+        [ComponentValue::Function(name, arguments)] => parse_function(name, arguments)?,
+        _ => return None,
+    };
+
+    // the result has to be a number or of a single type, `1px * 1px` isn't a value
+    let calc_type = node.calc_type()?;
+    if !calc_type.is_number() && calc_type.base_type().is_none() {
+        return None;
+    }
+    Some(node.simplify())
+}
+
+pub fn is_math_function(name: &str) -> bool {
+    name.eq_ignore_ascii_case("calc") || MathFunction::from_name(name).is_some()
+}
+
+fn parse_function(name: &str, arguments: &[ComponentValue]) -> Option<CalcNode> {
+    if name.eq_ignore_ascii_case("calc") {
+        return parse_calculation(arguments);
+    }
+
+    let mut function = MathFunction::from_name(name)?;
+    let mut arguments = arguments.split(|value| *value == ComponentValue::Comma).collect::<Vec<_>>();
+    if let MathFunction::Round(..) = function {
+        let strategy = match arguments.first().map(|argument| trim_whitespace(argument)) {
+            Some([ComponentValue::Identifier(strategy)]) => RoundingStrategy::from_name(strategy),
+            _ => None,
+        };
+        if let Some(strategy) = strategy {
+            function = MathFunction::Round(strategy);
+            arguments.remove(0);
+        }
+    }
+
+    let count = arguments.len();
+    let valid_count = match function {
+        MathFunction::Min | MathFunction::Max | MathFunction::Hypot => true,
+        MathFunction::Clamp => count == 3,
+        MathFunction::Round(..) | MathFunction::Log => count == 1 || count == 2,
+        MathFunction::Mod | MathFunction::Rem | MathFunction::Atan2 | MathFunction::Pow => count == 2,
+        _ => count == 1,
+    };
+    if !valid_count {
+        return None;
+    }
+
+    let arguments = arguments.into_iter().map(parse_calculation).collect::<Option<Vec<_>>>()?;
+    let node = CalcNode::Function(function, arguments);
+    // type checked as it's built so a bad argument doesn't get past a function
+    node.calc_type()?;
+    Some(node)
+}
+
+// a whole calculation, eg the inside of `calc()` or a single argument of `min()`
+fn parse_calculation(values: &[ComponentValue]) -> Option<CalcNode> {
+    let mut parser = CalcParser { values, position: 0 };
+    let node = parser.parse_sum()?;
+    parser.skip_whitespace();
+
+    if parser.position < values.len() {
+        return None;
+    }
+    node.calc_type()?;
+    Some(node)
+}
+
+struct CalcParser<'a> {
+    values: &'a [ComponentValue],
+    position: usize,
+}
+
+impl<'a> CalcParser<'a> {
+    fn peek(&self) -> Option<&'a ComponentValue> {
+        self.values.get(self.position)
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(ComponentValue::is_whitespace) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    // `+` and `-` need whitespace on both sides, `1px -2px` is two values
+    fn parse_sum(&mut self) -> Option<CalcNode> {
+        self.skip_whitespace();
+        let mut terms = vec![self.parse_product()?];
+
+        loop {
+            let start = self.position;
+            if !self.skip_whitespace() {
+                break;
+            }
+            let negate = match self.peek() {
+                Some(ComponentValue::Delim('+')) => false,
+                Some(ComponentValue::Delim('-')) => true,
+                _ => {
+                    self.position = start;
+                    break;
+                },
+            };
+            self.position += 1;
+            if !self.skip_whitespace() {
+                return None;
+            }
+
+            let term = self.parse_product()?;
+            terms.push(if negate { CalcNode::Negate(Box::new(term)) } else { term });
+        }
+
+        Some(if terms.len() == 1 { terms.remove(0) } else { CalcNode::Sum(terms) })
+    }
+
+    fn parse_product(&mut self) -> Option<CalcNode> {
+        let mut factors = vec![self.parse_value()?];
+
+        loop {
+            let start = self.position;
+            self.skip_whitespace();
+            let invert = match self.peek() {
+                Some(ComponentValue::Delim('*')) => false,
+                Some(ComponentValue::Delim('/')) => true,
+                _ => {
+                    self.position = start;
+                    break;
+                },
+            };
+            self.position += 1;
+            self.skip_whitespace();
+
+            let factor = self.parse_value()?;
+            factors.push(if invert { CalcNode::Invert(Box::new(factor)) } else { factor });
+        }
+
+        Some(if factors.len() == 1 { factors.remove(0) } else { CalcNode::Product(factors) })
+    }
+
+    fn parse_value(&mut self) -> Option<CalcNode> {
+        let value = self.peek()?;
+        self.position += 1;
+
+        match value {
+            ComponentValue::Number(number) => Some(CalcNode::Number(number.value as f32)),
+            ComponentValue::Percentage(number) => Some(CalcNode::Percentage(number.value as f32)),
+            ComponentValue::Dimension(number, unit) => parse_dimension(number.value as f32, unit),
+            ComponentValue::Identifier(name) => parse_constant(name),
+            ComponentValue::Block(BlockKind::Parenthesis, values) => parse_calculation(values),
+            ComponentValue::Function(name, arguments) => parse_function(name, arguments),
+            _ => None,
+        }
+    }
+}
+
+fn parse_dimension(value: f32, unit: &str) -> Option<CalcNode> {
+    if let Some(unit) = LengthUnit::from_name(unit) {
+        Some(CalcNode::Length(value, unit))
+    } else if let Some(unit) = AngleUnit::from_name(unit) {
+        Some(CalcNode::Angle(value, unit))
+    } else if let Some(unit) = TimeUnit::from_name(unit) {
+        Some(CalcNode::Time(value, unit))
+    } else if let Some(unit) = FrequencyUnit::from_name(unit) {
+        Some(CalcNode::Frequency(value, unit))
+    } else {
+        ResolutionUnit::from_name(unit).map(|unit| CalcNode::Resolution(value, unit))
+    }
+}
+
+fn parse_constant(name: &str) -> Option<CalcNode> {
+    let value = match name.to_ascii_lowercase().as_str() {
+        "e" => std::f32::consts::E,
+        "pi" => std::f32::consts::PI,
+        "infinity" => f32::INFINITY,
+        "-infinity" => f32::NEG_INFINITY,
+        "nan" => f32::NAN,
+        _ => return None,
+    };
+    Some(CalcNode::Number(value))
+}
@@ -0,0 +1,547 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::selectors::{self, Element};
+use crate::properties::{self, PROPERTIES};
+use crate::custom_properties;
+use crate::style_sheet::{StyleSheet, CssRule, Rule, AtRuleKind, AtRuleBlock, Property, Specificity, ComponentValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Origin {
+    UserAgent,
+    User,
+    Author,
+}
+
+// Finds the declarations that apply to an element and orders them by precedence.
+// `@import`s aren't followed, and the rules in `@media` and `@supports` blocks apply
+// whenever `condition` says so, which can capture the viewport and whatever else it needs.
+pub struct Cascade<'a> {
+    pub style_sheets: Vec<(Origin, &'a StyleSheet)>,
+    pub condition: Box<dyn Fn(&AtRuleKind) -> bool + 'a>,
+}
+
+impl<'a> Cascade<'a> {
+    // every conditional rule applies until `condition` is replaced
+    pub fn new() -> Cascade<'a> {
+        Cascade {
+            style_sheets: vec![],
+            condition: Box::new(|_| true),
+        }
+    }
+
+    pub fn set_condition<F: Fn(&AtRuleKind) -> bool + 'a>(&mut self, condition: F) {
+        self.condition = Box::new(condition);
+    }
+
+    // style sheets of the same origin are in source order, in the order they're added
+    pub fn add_style_sheet(&mut self, origin: Origin, style_sheet: &'a StyleSheet) {
+        self.style_sheets.push((origin, style_sheet));
+    }
+
+    // `inline_style` is the element's `style` attribute
+    pub fn cascade<E: Element>(&self, element: &E, inline_style: &'a [Property]) -> CascadedStyle<'a> {
+        let mut collector = Collector {
+            condition: &*self.condition,
+            element,
+            declarations: vec![],
+            layers: LayerTree::default(),
+            layer_path: vec![],
+            anonymous_layers: 0,
+        };
+
+        let mut layer_ranks: HashMap<Origin, HashMap<Vec<String>, usize>> = HashMap::new();
+        for &origin in &[Origin::UserAgent, Origin::User, Origin::Author] {
+            collector.layers = LayerTree::default();
+            for (_, style_sheet) in self.style_sheets.iter().filter(|(o, _)| *o == origin) {
+                collector.collect_rules(origin, &style_sheet.rules);
+            }
+
+            let mut ranks = HashMap::new();
+            collector.layers.rank(&mut vec![], &mut ranks);
+            layer_ranks.insert(origin, ranks);
+        }
+
+        let mut declarations = collector.declarations;
+        for property in inline_style {
+            declarations.push(CascadedDeclaration {
+                property,
+                origin: Origin::Author,
+                important: property.important,
+                inline: true,
+                layer: None,
+                specificity: Specificity::empty(),
+                source_order: declarations.len(),
+                layer_rank: 0,
+            });
+        }
+
+        let mut style = CascadedStyle::default();
+        for mut declaration in declarations {
+            declaration.layer_rank = layer_ranks[&declaration.origin][declaration.layer.as_deref().unwrap_or(&[])];
+            style.declarations.entry(property_key(&declaration.property.name))
+                .or_insert_with(Vec::new)
+                .push(declaration);
+        }
+
+        for declarations in style.declarations.values_mut() {
+            declarations.sort_by(|a, b| b.compare_precedence(a));
+        }
+
+        style
+    }
+}
+
+impl<'a> Default for Cascade<'a> {
+    fn default() -> Cascade<'a> {
+        Cascade::new()
+    }
+}
+
+// custom properties are case-sensitive, everything else isn't
+fn property_key(name: &str) -> String {
+    if name.starts_with("--") {
+        name.to_string()
+    } else {
+        name.to_ascii_lowercase()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CascadedStyle<'a> {
+    // the declarations for each property, from the winner down
+    pub declarations: HashMap<String, Vec<CascadedDeclaration<'a>>>,
+}
+
+impl<'a> CascadedStyle<'a> {
+    pub fn winner(&self, name: &str) -> Option<&CascadedDeclaration<'a>> {
+        self.declarations.get(&property_key(name)).and_then(|declarations| declarations.first())
+    }
+}
+
+// The value of every property on an element once the CSS-wide keywords are resolved and `var()`s
+// are substituted
+#[derive(Debug, Default, Clone)]
+pub struct SpecifiedStyle {
+    pub values: HashMap<String, Vec<ComponentValue>>,
+}
+
+impl SpecifiedStyle {
+    // `parent` is the specified style of the parent element, `None` for the root
+    pub fn resolve(cascaded: &CascadedStyle, parent: Option<&SpecifiedStyle>) -> SpecifiedStyle {
+        let mut style = SpecifiedStyle::default();
+
+        let inherited_custom = parent.into_iter()
+            .flat_map(|parent| parent.values.keys())
+            .filter(|name| name.starts_with("--"));
+        let names = PROPERTIES.iter()
+            .map(|property| property.name.to_string())
+            .chain(inherited_custom.cloned())
+            .chain(cascaded.declarations.keys().cloned())
+            .collect::<Vec<_>>();
+
+        for name in names {
+            if style.values.contains_key(&name) {
+                continue;
+            }
+
+            let declarations = cascaded.declarations.get(&name).map_or(&[][..], |declarations| &declarations[..]);
+            if let Some(value) = resolve_value(&name, declarations, parent) {
+                style.values.insert(name, value);
+            }
+        }
+
+        // custom properties first, the others can refer to them
+        custom_properties::resolve(&mut style.values);
+        let names = style.values.iter()
+            .filter(|(name, value)| !name.starts_with("--") && custom_properties::contains_var(value))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in names {
+            // invalid at computed-value time, which acts like `unset`
+            let value = custom_properties::substitute(&style.values[&name], &style.values)
+                .or_else(|| inherit_or_initial(&name, properties::is_inherited(&name), parent));
+            match value {
+                Some(value) => style.values.insert(name, value),
+                None => style.values.remove(&name),
+            };
+        }
+
+        style
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[ComponentValue]> {
+        self.values.get(name).map(Vec::as_slice)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CssWideKeyword {
+    Initial,
+    Inherit,
+    Unset,
+    Revert,
+    RevertLayer,
+}
+
+// the keyword has to be the whole value
+fn css_wide_keyword(value: &[ComponentValue]) -> Option<CssWideKeyword> {
+    let keyword = match value {
+        [ComponentValue::Identifier(keyword)] => keyword,
+        _ => return None,
+    };
+
+    match keyword.to_ascii_lowercase().as_str() {
+        "initial" => Some(CssWideKeyword::Initial),
+        "inherit" => Some(CssWideKeyword::Inherit),
+        "unset" => Some(CssWideKeyword::Unset),
+        "revert" => Some(CssWideKeyword::Revert),
+        "revert-layer" => Some(CssWideKeyword::RevertLayer),
+        _ => None,
+    }
+}
+
+// `declarations` are sorted from the winner down, `revert` and `revert-layer` fall back to the
+// declarations of the previous origin or layer, and `unset` when there are none left or when
+// they're used by the user agent
+fn resolve_value(name: &str, declarations: &[CascadedDeclaration], parent: Option<&SpecifiedStyle>) -> Option<Vec<ComponentValue>> {
+    let mut remaining = declarations.iter().collect::<Vec<_>>();
+    let keyword = loop {
+        let declaration = match remaining.first() {
+            Some(declaration) => *declaration,
+            None => break CssWideKeyword::Unset,
+        };
+
+        match css_wide_keyword(&declaration.property.value) {
+            None => return Some(declaration.property.value.clone()),
+            // there's no origin before the user agent's to go back to
+            Some(CssWideKeyword::Revert) if declaration.origin == Origin::UserAgent => break CssWideKeyword::Unset,
+            Some(CssWideKeyword::Revert) => remaining.retain(|d| d.origin != declaration.origin),
+            // Inline style is a layer of its own, above the unlayered author declarations. Once the
+            // user agent's layers run out it's `unset`, like `revert`.
+            Some(CssWideKeyword::RevertLayer) => remaining.retain(|d| {
+                let same_layer = d.origin == declaration.origin && d.layer == declaration.layer && d.inline == declaration.inline;
+                !same_layer && (declaration.origin != Origin::UserAgent || d.origin == Origin::UserAgent)
+            }),
+            Some(keyword) => break keyword,
+        }
+    };
+
+    let inherit = match keyword {
+        CssWideKeyword::Inherit => true,
+        CssWideKeyword::Unset => properties::is_inherited(name),
+        _ => false,
+    };
+    inherit_or_initial(name, inherit, parent)
+}
+
+// the root inherits initial values
+fn inherit_or_initial(name: &str, inherit: bool, parent: Option<&SpecifiedStyle>) -> Option<Vec<ComponentValue>> {
+    match parent.and_then(|parent| parent.get(name)) {
+        Some(value) if inherit => Some(value.to_vec()),
+        _ => properties::lookup(name).map(|property| property.initial_value()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CascadedDeclaration<'a> {
+    pub property: &'a Property,
+    pub origin: Origin,
+    pub important: bool,
+    // from the element's `style` attribute
+    pub inline: bool,
+    // the full path of the cascade layer, `None` outside of any
+    pub layer: Option<Vec<String>>,
+    // the highest specificity of the rule's selectors that matched
+    pub specificity: Specificity,
+    pub source_order: usize,
+    // the layer's position in its origin, later layers and unlayered declarations rank higher
+    layer_rank: usize,
+}
+
+impl<'a> CascadedDeclaration<'a> {
+    // `Greater` if `self` wins over `other`
+    pub fn compare_precedence(&self, other: &CascadedDeclaration) -> Ordering {
+        self.origin_rank().cmp(&other.origin_rank())
+            .then(self.inline.cmp(&other.inline))
+            .then_with(|| match self.important {
+                // important declarations in earlier layers win
+                true => other.layer_rank.cmp(&self.layer_rank),
+                false => self.layer_rank.cmp(&other.layer_rank),
+            })
+            .then(self.specificity.cmp(&other.specificity))
+            .then(self.source_order.cmp(&other.source_order))
+    }
+
+    // the origin order flips for important declarations
+    fn origin_rank(&self) -> u8 {
+        match (self.important, self.origin) {
+            (false, Origin::UserAgent) => 0,
+            (false, Origin::User) => 1,
+            (false, Origin::Author) => 2,
+            (true, Origin::Author) => 3,
+            (true, Origin::User) => 4,
+            (true, Origin::UserAgent) => 5,
+        }
+    }
+}
+
+struct Collector<'a, 'e, E> {
+    condition: &'e dyn Fn(&AtRuleKind) -> bool,
+    element: &'e E,
+    declarations: Vec<CascadedDeclaration<'a>>,
+    layers: LayerTree,
+    layer_path: Vec<String>,
+    anonymous_layers: usize,
+}
+
+impl<'a, 'e, E: Element> Collector<'a, 'e, E> {
+    fn collect_rules(&mut self, origin: Origin, rules: &'a [CssRule]) {
+        for rule in rules {
+            match rule {
+                CssRule::Style(rule) => self.collect_rule(origin, rule),
+                CssRule::At(at_rule) => match (&at_rule.kind, &at_rule.block) {
+                    (AtRuleKind::Media { .. }, Some(AtRuleBlock::Rules(rules)))
+                    | (AtRuleKind::Supports { .. }, Some(AtRuleBlock::Rules(rules)))
+                        if (self.condition)(&at_rule.kind) => self.collect_rules(origin, rules),
+                    (AtRuleKind::Layer { names }, Some(AtRuleBlock::Rules(rules))) => {
+                        let name = match names.first() {
+                            Some(name) => layer_path(name),
+                            None => {
+                                // anonymous layers can't be referred to again, a name with a space can't be written
+                                self.anonymous_layers += 1;
+                                vec![format!("anonymous {}", self.anonymous_layers)]
+                            },
+                        };
+
+                        let depth = self.layer_path.len();
+                        self.layer_path.extend(name);
+                        self.layers.declare(&self.layer_path);
+                        self.collect_rules(origin, rules);
+                        self.layer_path.truncate(depth);
+                    },
+                    // `@layer a, b;` only fixes the order of the layers
+                    (AtRuleKind::Layer { names }, None) => {
+                        for name in names {
+                            let mut path = self.layer_path.clone();
+                            path.extend(layer_path(name));
+                            self.layers.declare(&path);
+                        }
+                    },
+                    _ => (),
+                },
+            }
+        }
+    }
+
+    fn collect_rule(&mut self, origin: Origin, rule: &'a Rule) {
+        let specificity = rule.selectors.iter()
+            .filter(|selector| selectors::matches(selector, self.element))
+            .map(|selector| selector.specificity())
+            .max();
+
+        let specificity = match specificity {
+            Some(specificity) => specificity,
+            None => return,
+        };
+
+        for property in &rule.properties {
+            let source_order = self.declarations.len();
+            self.declarations.push(CascadedDeclaration {
+                property,
+                origin,
+                important: property.important,
+                inline: false,
+                layer: if self.layer_path.is_empty() { None } else { Some(self.layer_path.clone()) },
+                specificity,
+                source_order,
+                layer_rank: 0,
+            });
+        }
+    }
+}
+
+fn layer_path(name: &str) -> Vec<String> {
+    name.split('.').map(|part| part.trim().to_string()).collect()
+}
+
+// Layers in the order they were first declared, each with its sublayers
+#[derive(Debug, Default)]
+struct LayerTree {
+    children: Vec<(String, LayerTree)>,
+}
+
+impl LayerTree {
+    fn declare(&mut self, path: &[String]) {
+        let (name, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        let index = match self.children.iter().position(|(child, _)| child == name) {
+            Some(index) => index,
+            None => {
+                self.children.push((name.clone(), LayerTree::default()));
+                self.children.len() - 1
+            },
+        };
+
+        self.children[index].1.declare(rest);
+    }
+
+    // sublayers rank below the declarations directly in their parent, the root being the unlayered ones
+    fn rank(&self, path: &mut Vec<String>, ranks: &mut HashMap<Vec<String>, usize>) {
+        for (name, child) in &self.children {
+            path.push(name.clone());
+            child.rank(path, ranks);
+            path.pop();
+        }
+
+        let rank = ranks.len();
+        ranks.insert(path.clone(), rank);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cascade, Origin, SpecifiedStyle};
+    use crate::dom::Document;
+    use crate::style_sheet::{AtRuleKind, StyleSheet, CssRule, Property};
+
+    fn parse(css: &str) -> StyleSheet {
+        let output = crate::parse_str(css);
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        output.stylesheet
+    }
+
+    fn inline_style(declarations: &str) -> Vec<Property> {
+        match parse(&format!("p {{ {} }}", declarations)).rules.into_iter().next() {
+            Some(CssRule::Style(rule)) => rule.properties,
+            rule => panic!("not a style rule: {:?}", rule),
+        }
+    }
+
+    // the specified values of a `<p>` inside an `<html>`, by name
+    fn specified(cascade: &Cascade, inline_style: &[Property], names: &[&str]) -> Vec<String> {
+        let mut document = Document::new();
+        let html = document.append_element(None, "html");
+        let p = document.append_element(Some(html), "p");
+
+        let root = SpecifiedStyle::resolve(&cascade.cascade(&document.element(html), &[]), None);
+        let style = SpecifiedStyle::resolve(&cascade.cascade(&document.element(p), inline_style), Some(&root));
+        names.iter()
+            .map(|name| match style.get(name) {
+                Some(value) => value.iter().map(|value| value.to_string()).collect(),
+                None => "<none>".to_string(),
+            })
+            .collect()
+    }
+
+    // the value of `name` with the user agent, user and author style sheets and inline style given
+    fn cascaded(user_agent: &str, user: &str, author: &str, inline: &str, name: &str) -> String {
+        let style_sheets = [parse(user_agent), parse(user), parse(author)];
+        let mut cascade = Cascade::new();
+        cascade.add_style_sheet(Origin::UserAgent, &style_sheets[0]);
+        cascade.add_style_sheet(Origin::User, &style_sheets[1]);
+        cascade.add_style_sheet(Origin::Author, &style_sheets[2]);
+        specified(&cascade, &inline_style(inline), &[name]).remove(0)
+    }
+
+    fn winner(css: &str, name: &str) -> String {
+        cascaded("", "", css, "", name)
+    }
+
+    #[test]
+    fn condition_can_capture_state() {
+        let style_sheet = parse("p { width: 1px } @media (min-width: 600px) { p { width: 2px } } @supports (display: grid) { p { height: 3px } }");
+        let viewport_width = 500;
+
+        let mut cascade = Cascade::new();
+        cascade.add_style_sheet(Origin::Author, &style_sheet);
+        cascade.set_condition(|kind| match kind {
+            AtRuleKind::Media { queries } => queries.iter().any(|query| query == "(min-width: 600px)") && viewport_width >= 600,
+            _ => true,
+        });
+        assert_eq!(specified(&cascade, &[], &["width", "height"]), ["1px", "3px"]);
+
+        cascade.set_condition(|_| true);
+        assert_eq!(specified(&cascade, &[], &["width", "height"]), ["2px", "3px"]);
+    }
+
+    #[test]
+    fn later_layers_win_unless_important() {
+        assert_eq!(winner("@layer a { p { width: 1px } } @layer b { p { width: 2px } }", "width"), "2px");
+        assert_eq!(winner("@layer b, a; @layer a { p { width: 1px } } @layer b { p { width: 2px } }", "width"), "1px");
+        assert_eq!(winner("p { width: 3px } @layer a { #x, p { width: 1px } }", "width"), "3px");
+        assert_eq!(winner("@layer a { p { width: 1px !important } } @layer b { p { width: 2px !important } }", "width"), "1px");
+        assert_eq!(winner("@layer a { p { width: 1px !important } } p { width: 2px !important }", "width"), "1px");
+        assert_eq!(winner("@layer a { @layer b { p { width: 1px } } p { width: 2px } }", "width"), "2px");
+        assert_eq!(winner("@layer a.b { p { width: 1px } } @layer a { p { width: 2px } }", "width"), "2px");
+    }
+
+    #[test]
+    fn origins_and_importance_are_ordered() {
+        assert_eq!(cascaded("p { width: 1px }", "p { width: 2px }", "", "", "width"), "2px");
+        assert_eq!(cascaded("", "p { width: 2px }", "p { width: 3px }", "", "width"), "3px");
+        assert_eq!(cascaded("", "p { width: 2px !important }", "p { width: 3px !important }", "", "width"), "2px");
+        assert_eq!(cascaded("p { width: 1px !important }", "p { width: 2px !important }", "", "", "width"), "1px");
+        assert_eq!(cascaded("", "", "#x, p { width: 3px }", "width: 4px", "width"), "4px");
+        assert_eq!(cascaded("", "", "p { width: 3px !important }", "width: 4px", "width"), "3px");
+        assert_eq!(winner("p.a { width: 1px } p { width: 2px }", "width"), "2px");
+        assert_eq!(winner("html p { width: 1px } p { width: 2px }", "width"), "1px");
+        assert_eq!(winner("p { width: 1px } p { width: 2px }", "width"), "2px");
+    }
+
+    #[test]
+    fn css_wide_keywords() {
+        let css = "html { color: red; width: 5px } p { color: initial; width: inherit; font-size: unset; height: unset }";
+        assert_eq!(winner(css, "color"), "canvastext");
+        assert_eq!(winner(css, "width"), "5px");
+        assert_eq!(winner(css, "font-size"), "medium");
+        assert_eq!(winner(css, "height"), "auto");
+    }
+
+    #[test]
+    fn revert_rolls_back_to_the_previous_origin() {
+        assert_eq!(cascaded("p { width: 1px }", "p { width: 2px }", "p { width: revert }", "", "width"), "2px");
+        assert_eq!(cascaded("p { width: 1px }", "", "p { width: revert }", "", "width"), "1px");
+        assert_eq!(cascaded("", "", "p { width: 1px } p { width: revert }", "", "width"), "auto");
+        assert_eq!(cascaded("html { color: red }", "", "p { color: revert }", "", "color"), "red");
+    }
+
+    #[test]
+    fn revert_in_the_user_agent_origin_is_unset() {
+        assert_eq!(cascaded("p { width: revert !important }", "", "p { width: 3px }", "", "width"), "auto");
+        assert_eq!(cascaded("p { width: revert-layer !important }", "", "p { width: 3px }", "", "width"), "auto");
+        assert_eq!(cascaded("html { color: red } p { color: revert !important }", "", "p { color: blue }", "", "color"), "red");
+    }
+
+    #[test]
+    fn revert_layer_rolls_back_to_the_previous_layer() {
+        let layers = "@layer a { p { width: 1px } } @layer b { p { width: revert-layer } }";
+        assert_eq!(winner(layers, "width"), "1px");
+        assert_eq!(winner("@layer a { p { width: 1px } } p { width: revert-layer }", "width"), "1px");
+        assert_eq!(cascaded("p { width: 1px }", "", "@layer a { p { width: revert-layer } }", "", "width"), "1px");
+        assert_eq!(winner("@layer a { p { width: revert-layer } } @layer a { p { height: 1px } }", "width"), "auto");
+    }
+
+    #[test]
+    fn inline_style_is_its_own_layer() {
+        assert_eq!(cascaded("", "", "p { width: 3px }", "width: revert-layer", "width"), "3px");
+        assert_eq!(cascaded("p { width: 1px }", "", "", "width: revert", "width"), "1px");
+        assert_eq!(cascaded("", "", "@layer a { p { width: 2px } }", "width: revert-layer", "width"), "2px");
+    }
+
+    #[test]
+    fn substitutes_var_per_element() {
+        let css = "html { --gap: 4px; --size: var(--gap); color: red } \
+            p { --gap: 10px; width: var(--size); height: var(--gap); margin-top: var(--missing); color: var(--missing) }";
+        assert_eq!(winner(css, "width"), "4px");
+        assert_eq!(winner(css, "height"), "10px");
+        // invalid at computed-value time acts like `unset`
+        assert_eq!(winner(css, "margin-top"), "0");
+        assert_eq!(winner(css, "color"), "red");
+        assert_eq!(winner("p { --a: var(--b); --b: var(--a); width: var(--a, 1px) }", "width"), "1px");
+        assert_eq!(winner("p { --a: var(--b); --b: var(--a) }", "--a"), "<none>");
+    }
+}
@@ -0,0 +1,647 @@
+// Color space conversion, gamut mapping and interpolation math from CSS Color 4 and 5, conversions
+// go through XYZ with a D65 white point. Computed in f64, components are f32 outside of this module.
+use crate::style_sheet::{ColorSpace, AbsoluteColor, HueInterpolation};
+
+type Matrix = [[f64; 3]; 3];
+
+const LINEAR_SRGB_TO_XYZ: Matrix = [
+    [0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+    [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+    [0.01933081871559182, 0.11919477979462598, 0.9505321522496607],
+];
+
+const XYZ_TO_LINEAR_SRGB: Matrix = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+];
+
+const XYZ_TO_LINEAR_DISPLAY_P3: Matrix = [
+    [2.493496911941425, -0.9313836179191239, -0.40271078445071684],
+    [-0.8294889695615747, 1.7626640603183463, 0.023624685841943577],
+    [0.03584583024378447, -0.07617238926804182, 0.9568845240076872],
+];
+
+const LINEAR_DISPLAY_P3_TO_XYZ: Matrix = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+const LINEAR_A98_RGB_TO_XYZ: Matrix = [
+    [0.5766690429101305, 0.1855582379065463, 0.1882286462349947],
+    [0.29734497525053605, 0.6273635662554661, 0.07529145849399788],
+    [0.02703136138641234, 0.07068885253582723, 0.9913375368376388],
+];
+
+const XYZ_TO_LINEAR_A98_RGB: Matrix = [
+    [2.0415879038107465, -0.5650069742788596, -0.34473135077832956],
+    [-0.9692436362808795, 1.8759675015077202, 0.04155505740717557],
+    [0.013444280632031142, -0.11836239223101838, 1.0151749943912054],
+];
+
+// to and from XYZ D50
+const LINEAR_PROPHOTO_RGB_TO_XYZ_D50: Matrix = [
+    [0.7977604896723027, 0.13518583717574031, 0.0313493495815248],
+    [0.2880711282292934, 0.7118432178101014, 0.00008565396060525902],
+    [0.0, 0.0, 0.8251046025104601],
+];
+
+const XYZ_D50_TO_LINEAR_PROPHOTO_RGB: Matrix = [
+    [1.3457989731028281, -0.25558010007997534, -0.05110628506753401],
+    [-0.5446224939028347, 1.5082327413132781, 0.02053603239147973],
+    [0.0, 0.0, 1.2119675456389454],
+];
+
+const LINEAR_REC2020_TO_XYZ: Matrix = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+
+const XYZ_TO_LINEAR_REC2020: Matrix = [
+    [1.716651187971268, -0.355670783776392, -0.25336628137366],
+    [-0.666684351832489, 1.616481236634939, 0.0157685458139111],
+    [0.017639857445311, -0.042770613257809, 0.942103121235474],
+];
+
+// Bradford chromatic adaptation
+const D50_TO_D65: Matrix = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [-0.0283697093338637, 1.0099953980813041, 0.021041441191917323],
+    [0.012314014864481998, -0.020507649298898964, 1.330365926242124],
+];
+
+const D65_TO_D50: Matrix = [
+    [1.0479297925449969, 0.022946870601609652, -0.05019226628920524],
+    [0.02962780877005599, 0.9904344267538799, -0.017073799063418826],
+    [-0.009243040646204504, 0.015055191490298152, 0.7518742814281371],
+];
+
+const XYZ_TO_LMS: Matrix = [
+    [0.819022437996703, 0.3619062600528904, -0.1288737815209879],
+    [0.0329836539323885, 0.9292868615863434, 0.0361446663506424],
+    [0.0481771893596242, 0.2642395317527308, 0.6335478284694309],
+];
+
+const LMS_TO_OKLAB: Matrix = [
+    [0.210454268309314, 0.7936177747023054, -0.0040720430116193],
+    [1.9779985324311684, -2.42859224204858, 0.450593709617411],
+    [0.0259040424655478, 0.7827717124575296, -0.8086757549230774],
+];
+
+const OKLAB_TO_LMS: Matrix = [
+    [1.0, 0.3963377773761749, 0.2158037573099136],
+    [1.0, -0.1055613458156586, -0.0638541728258133],
+    [1.0, -0.0894841775298119, -1.2914855480194092],
+];
+
+const LMS_TO_XYZ: Matrix = [
+    [1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+    [-0.0405757452148008, 1.112286803280317, -0.0717110580655164],
+    [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816],
+];
+
+const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+const LAB_KAPPA: f64 = 24389.0 / 27.0;
+const LAB_EPSILON: f64 = 216.0 / 24389.0;
+
+// The hue is NaN when it's powerless, for achromatic colors
+pub(crate) fn convert(from: ColorSpace, components: [f32; 3], to: ColorSpace) -> [f32; 3] {
+    if from == to {
+        return components;
+    }
+
+    let components = components.map(f64::from);
+    let converted = match (from, to) {
+        // skip XYZ between the sRGB based spaces
+        (ColorSpace::Hsl, ColorSpace::Srgb) => hsl_to_srgb(components),
+        (ColorSpace::Hwb, ColorSpace::Srgb) => hwb_to_srgb(components),
+        (ColorSpace::Srgb, ColorSpace::Hsl) => srgb_to_hsl(components),
+        (ColorSpace::Srgb, ColorSpace::Hwb) => srgb_to_hwb(components),
+        (ColorSpace::Hsl, ColorSpace::Hwb) => srgb_to_hwb(hsl_to_srgb(components)),
+        (ColorSpace::Hwb, ColorSpace::Hsl) => srgb_to_hsl(hwb_to_srgb(components)),
+        _ => from_xyz_d65(to, to_xyz_d65(from, components)),
+    };
+
+    converted.map(|value| value as f32)
+}
+
+// CSS Color 4 gamut mapping, the OkLCh chroma is lowered until clipping the color changes it by less
+// than a just noticeable difference
+pub(crate) fn map_to_gamut(color: &AbsoluteColor, space: ColorSpace) -> AbsoluteColor {
+    const JND: f32 = 0.02;
+    const EPSILON: f32 = 0.0001;
+
+    // hsl and hwb share the gamut of sRGB, the other spaces without one are unbounded
+    let destination = match space {
+        ColorSpace::Hsl | ColorSpace::Hwb => ColorSpace::Srgb,
+        space => space,
+    };
+    if !is_bounded(destination) {
+        return color.to_space(space);
+    }
+
+    let converted = color.to_space(destination);
+    let origin = color.to_space(ColorSpace::Oklch);
+    let [lightness, chroma, hue] = origin.values();
+    let result = if lightness >= 1.0 || lightness <= 0.0 {
+        // white or black
+        let value = if lightness >= 1.0 { 1.0 } else { 0.0 };
+        AbsoluteColor { space: destination, components: [Some(value); 3], alpha: color.alpha, legacy: false }
+    } else if is_in_gamut(&converted) {
+        converted
+    } else {
+        let mut current = origin;
+        let mut clipped = clip(&converted);
+        if delta_e_ok(&clipped, &current) >= JND {
+            let (mut min, mut max) = (0.0, chroma);
+            let mut min_in_gamut = true;
+            while max - min > EPSILON {
+                let chroma = (min + max) / 2.0;
+                current.components = [Some(lightness), Some(chroma), Some(hue)];
+                let converted = current.to_space(destination);
+                if min_in_gamut && is_in_gamut(&converted) {
+                    min = chroma;
+                    continue;
+                }
+
+                clipped = clip(&converted);
+                let delta = delta_e_ok(&clipped, &current);
+                if delta < JND {
+                    if JND - delta < EPSILON {
+                        break;
+                    }
+                    min_in_gamut = false;
+                    min = chroma;
+                } else {
+                    max = chroma;
+                }
+            }
+        }
+        clipped
+    };
+
+    result.to_space(space)
+}
+
+pub(crate) fn is_in_gamut(color: &AbsoluteColor) -> bool {
+    // rounding errors put colors at the edge of the gamut slightly out of it
+    const EPSILON: f32 = 0.000075;
+
+    match color.space {
+        ColorSpace::Hsl | ColorSpace::Hwb => is_in_gamut(&color.to_space(ColorSpace::Srgb)),
+        space if is_bounded(space) => color.values().iter().all(|&c| (-EPSILON..=1.0 + EPSILON).contains(&c)),
+        _ => true,
+    }
+}
+
+// the RGB spaces, whose components go from 0 to 1
+fn is_bounded(space: ColorSpace) -> bool {
+    matches!(
+        space,
+        ColorSpace::Srgb | ColorSpace::SrgbLinear | ColorSpace::DisplayP3 | ColorSpace::A98Rgb
+            | ColorSpace::ProphotoRgb | ColorSpace::Rec2020
+    )
+}
+
+fn clip(color: &AbsoluteColor) -> AbsoluteColor {
+    AbsoluteColor {
+        components: color.components.map(|component| component.map(|c| c.clamp(0.0, 1.0))),
+        ..*color
+    }
+}
+
+// the distance between two colors in OkLab
+fn delta_e_ok(a: &AbsoluteColor, b: &AbsoluteColor) -> f32 {
+    let [a, b] = [a, b].map(|color| color.to_space(ColorSpace::Oklab).values());
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+// CSS Color 5 `color-mix()`, percentages are from 0 to 100 and `None` when left out.
+// `None` if both percentages are zero.
+pub(crate) fn mix(
+    space: ColorSpace,
+    hue_interpolation: HueInterpolation,
+    (first, first_percentage): (&AbsoluteColor, Option<f32>),
+    (second, second_percentage): (&AbsoluteColor, Option<f32>),
+) -> Option<AbsoluteColor> {
+    let (first_percentage, second_percentage) = match (first_percentage, second_percentage) {
+        (None, None) => (50.0, 50.0),
+        (Some(first), None) => (first, 100.0 - first),
+        (None, Some(second)) => (100.0 - second, second),
+        (Some(first), Some(second)) => (first, second),
+    };
+    let total = first_percentage + second_percentage;
+    if total <= 0.0 {
+        return None;
+    }
+    // percentages adding up to less than 100% make the result transparent
+    let alpha_multiplier = (total / 100.0).min(1.0);
+    let weight = second_percentage / total;
+
+    let first = to_interpolation_space(first, space);
+    let second = to_interpolation_space(second, space);
+    let hue_index = match space {
+        ColorSpace::Hsl | ColorSpace::Hwb => Some(0),
+        ColorSpace::Lch | ColorSpace::Oklch => Some(2),
+        _ => None,
+    };
+
+    let (first_alpha, second_alpha) = fill_missing(first.alpha, second.alpha);
+    let alpha = first_alpha.map(|first_alpha| interpolate(first_alpha, second_alpha.unwrap_or(first_alpha), weight));
+
+    let mut components = [None; 3];
+    for (index, component) in components.iter_mut().enumerate() {
+        let (a, b) = match fill_missing(first.components[index], second.components[index]) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+
+        *component = Some(match hue_index {
+            Some(hue_index) if hue_index == index => {
+                let (a, b) = fix_hues(a, b, hue_interpolation);
+                interpolate(a, b, weight).rem_euclid(360.0)
+            },
+            // in premultiplied alpha
+            _ => {
+                let premultiplied = interpolate(
+                    a * first_alpha.unwrap_or(1.0),
+                    b * second_alpha.unwrap_or(1.0),
+                    weight,
+                );
+                match alpha {
+                    Some(alpha) if alpha != 0.0 => premultiplied / alpha,
+                    _ => premultiplied,
+                }
+            },
+        });
+    }
+
+    Some(AbsoluteColor { space, components, alpha: alpha.map(|alpha| alpha * alpha_multiplier), legacy: false })
+}
+
+// a component missing in one color takes its value from the other
+fn fill_missing(a: Option<f32>, b: Option<f32>) -> (Option<f32>, Option<f32>) {
+    (a.or(b), b.or(a))
+}
+
+fn interpolate(a: f32, b: f32, weight: f32) -> f32 {
+    a + (b - a) * weight
+}
+
+// adjusts the hues so that interpolating between them goes the requested way around the hue circle
+fn fix_hues(a: f32, b: f32, hue_interpolation: HueInterpolation) -> (f32, f32) {
+    let (a, b) = (a.rem_euclid(360.0), b.rem_euclid(360.0));
+    let delta = b - a;
+    match hue_interpolation {
+        HueInterpolation::Shorter if delta > 180.0 => (a + 360.0, b),
+        HueInterpolation::Shorter if delta < -180.0 => (a, b + 360.0),
+        HueInterpolation::Longer if 0.0 < delta && delta < 180.0 => (a + 360.0, b),
+        HueInterpolation::Longer if -180.0 < delta && delta <= 0.0 => (a, b + 360.0),
+        HueInterpolation::Increasing if b < a => (a, b + 360.0),
+        HueInterpolation::Decreasing if a < b => (a + 360.0, b),
+        _ => (a, b),
+    }
+}
+
+// Missing components stay missing in the analogous component of the new space, eg the hue of `hsl()`
+// and `oklch()`
+fn to_interpolation_space(color: &AbsoluteColor, space: ColorSpace) -> AbsoluteColor {
+    let mut converted = color.to_space(space);
+    for (index, component) in color.components.iter().enumerate() {
+        let analogous = match analogous_component(color.space, index) {
+            Some(analogous) if component.is_none() => analogous,
+            _ => continue,
+        };
+        if let Some(target) = (0..3).find(|&target| analogous_component(space, target) == Some(analogous)) {
+            converted.components[target] = None;
+        }
+    }
+
+    converted
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Analogous {
+    Red,
+    Green,
+    Blue,
+    Lightness,
+    Colorfulness,
+    Hue,
+    OpponentA,
+    OpponentB,
+}
+
+fn analogous_component(space: ColorSpace, index: usize) -> Option<Analogous> {
+    match (space, index) {
+        (ColorSpace::Hsl, 0) | (ColorSpace::Hwb, 0) | (ColorSpace::Lch, 2) | (ColorSpace::Oklch, 2) => Some(Analogous::Hue),
+        (ColorSpace::Hsl, 1) | (ColorSpace::Lch, 1) | (ColorSpace::Oklch, 1) => Some(Analogous::Colorfulness),
+        (ColorSpace::Hsl, 2) | (ColorSpace::Lab, 0) | (ColorSpace::Lch, 0) | (ColorSpace::Oklab, 0)
+        | (ColorSpace::Oklch, 0) => Some(Analogous::Lightness),
+        (ColorSpace::Lab, 1) | (ColorSpace::Oklab, 1) => Some(Analogous::OpponentA),
+        (ColorSpace::Lab, 2) | (ColorSpace::Oklab, 2) => Some(Analogous::OpponentB),
+        (ColorSpace::Hwb, _) => None,
+        // the RGB and XYZ spaces
+        (_, 0) => Some(Analogous::Red),
+        (_, 1) => Some(Analogous::Green),
+        (_, _) => Some(Analogous::Blue),
+    }
+}
+
+fn to_xyz_d65(space: ColorSpace, components: [f64; 3]) -> [f64; 3] {
+    match space {
+        ColorSpace::Srgb => multiply(&LINEAR_SRGB_TO_XYZ, to_linear_srgb(components)),
+        ColorSpace::Hsl => to_xyz_d65(ColorSpace::Srgb, hsl_to_srgb(components)),
+        ColorSpace::Hwb => to_xyz_d65(ColorSpace::Srgb, hwb_to_srgb(components)),
+        ColorSpace::SrgbLinear => multiply(&LINEAR_SRGB_TO_XYZ, components),
+        // display-p3 shares the sRGB transfer function
+        ColorSpace::DisplayP3 => multiply(&LINEAR_DISPLAY_P3_TO_XYZ, to_linear_srgb(components)),
+        ColorSpace::A98Rgb => {
+            multiply(&LINEAR_A98_RGB_TO_XYZ, components.map(|c| c.signum() * c.abs().powf(563.0 / 256.0)))
+        },
+        ColorSpace::ProphotoRgb => {
+            let linear = components.map(|c| match c.abs() {
+                abs if abs <= 16.0 / 512.0 => c / 16.0,
+                abs => c.signum() * abs.powf(1.8),
+            });
+            multiply(&D50_TO_D65, multiply(&LINEAR_PROPHOTO_RGB_TO_XYZ_D50, linear))
+        },
+        ColorSpace::Rec2020 => {
+            let (alpha, beta) = (1.09929682680944, 0.018053968510807);
+            let linear = components.map(|c| match c.abs() {
+                abs if abs < beta * 4.5 => c / 4.5,
+                abs => c.signum() * ((abs + alpha - 1.0) / alpha).powf(1.0 / 0.45),
+            });
+            multiply(&LINEAR_REC2020_TO_XYZ, linear)
+        },
+        ColorSpace::XyzD50 => multiply(&D50_TO_D65, components),
+        ColorSpace::XyzD65 => components,
+        ColorSpace::Lab => multiply(&D50_TO_D65, lab_to_xyz_d50(components)),
+        ColorSpace::Lch => to_xyz_d65(ColorSpace::Lab, polar_to_rectangular(components)),
+        ColorSpace::Oklab => {
+            let lms = multiply(&OKLAB_TO_LMS, components).map(|c| c * c * c);
+            multiply(&LMS_TO_XYZ, lms)
+        },
+        ColorSpace::Oklch => to_xyz_d65(ColorSpace::Oklab, polar_to_rectangular(components)),
+    }
+}
+
+fn from_xyz_d65(space: ColorSpace, xyz: [f64; 3]) -> [f64; 3] {
+    match space {
+        ColorSpace::Srgb => from_linear_srgb(multiply(&XYZ_TO_LINEAR_SRGB, xyz)),
+        ColorSpace::Hsl => srgb_to_hsl(from_xyz_d65(ColorSpace::Srgb, xyz)),
+        ColorSpace::Hwb => srgb_to_hwb(from_xyz_d65(ColorSpace::Srgb, xyz)),
+        ColorSpace::SrgbLinear => multiply(&XYZ_TO_LINEAR_SRGB, xyz),
+        ColorSpace::DisplayP3 => from_linear_srgb(multiply(&XYZ_TO_LINEAR_DISPLAY_P3, xyz)),
+        ColorSpace::A98Rgb => {
+            multiply(&XYZ_TO_LINEAR_A98_RGB, xyz).map(|c| c.signum() * c.abs().powf(256.0 / 563.0))
+        },
+        ColorSpace::ProphotoRgb => {
+            multiply(&XYZ_D50_TO_LINEAR_PROPHOTO_RGB, multiply(&D65_TO_D50, xyz)).map(|c| match c.abs() {
+                abs if abs >= 1.0 / 512.0 => c.signum() * abs.powf(1.0 / 1.8),
+                _ => c * 16.0,
+            })
+        },
+        ColorSpace::Rec2020 => {
+            let (alpha, beta) = (1.09929682680944, 0.018053968510807);
+            multiply(&XYZ_TO_LINEAR_REC2020, xyz).map(|c| match c.abs() {
+                abs if abs > beta => c.signum() * (alpha * abs.powf(0.45) - (alpha - 1.0)),
+                _ => c * 4.5,
+            })
+        },
+        ColorSpace::XyzD50 => multiply(&D65_TO_D50, xyz),
+        ColorSpace::XyzD65 => xyz,
+        ColorSpace::Lab => xyz_d50_to_lab(multiply(&D65_TO_D50, xyz)),
+        ColorSpace::Lch => rectangular_to_polar(from_xyz_d65(ColorSpace::Lab, xyz), 0.0015),
+        ColorSpace::Oklab => multiply(&LMS_TO_OKLAB, multiply(&XYZ_TO_LMS, xyz).map(f64::cbrt)),
+        ColorSpace::Oklch => rectangular_to_polar(from_xyz_d65(ColorSpace::Oklab, xyz), 0.000004),
+    }
+}
+
+fn to_linear_srgb(components: [f64; 3]) -> [f64; 3] {
+    components.map(|c| match c.abs() {
+        abs if abs <= 0.04045 => c / 12.92,
+        abs => c.signum() * ((abs + 0.055) / 1.055).powf(2.4),
+    })
+}
+
+fn from_linear_srgb(components: [f64; 3]) -> [f64; 3] {
+    components.map(|c| match c.abs() {
+        abs if abs <= 0.0031308 => c * 12.92,
+        abs => c.signum() * (1.055 * abs.powf(1.0 / 2.4) - 0.055),
+    })
+}
+
+// saturation and lightness from 0 to 100
+fn hsl_to_srgb([hue, saturation, lightness]: [f64; 3]) -> [f64; 3] {
+    let (saturation, lightness) = (saturation / 100.0, lightness / 100.0);
+    let hue = hue.rem_euclid(360.0);
+    let a = saturation * lightness.min(1.0 - lightness);
+    let channel = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+
+    [channel(0.0), channel(8.0), channel(4.0)]
+}
+
+fn srgb_to_hsl([red, green, blue]: [f64; 3]) -> [f64; 3] {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let lightness = (min + max) / 2.0;
+    let delta = max - min;
+
+    let mut hue = f64::NAN;
+    let mut saturation = 0.0;
+    if delta != 0.0 {
+        saturation = match lightness {
+            l if l == 0.0 || l == 1.0 => 0.0,
+            l => (max - l) / l.min(1.0 - l),
+        };
+        hue = 60.0 * match max {
+            max if max == red => (green - blue) / delta + if green < blue { 6.0 } else { 0.0 },
+            max if max == green => (blue - red) / delta + 2.0,
+            _ => (red - green) / delta + 4.0,
+        };
+    }
+
+    // out of gamut colors can have a negative saturation
+    if saturation < 0.0 {
+        hue += 180.0;
+        saturation = saturation.abs();
+    }
+
+    [hue.rem_euclid(360.0), saturation * 100.0, lightness * 100.0]
+}
+
+fn srgb_to_hwb(srgb: [f64; 3]) -> [f64; 3] {
+    let [hue, _, _] = srgb_to_hsl(srgb);
+    let whiteness = srgb[0].min(srgb[1]).min(srgb[2]);
+    let blackness = 1.0 - srgb[0].max(srgb[1]).max(srgb[2]);
+    let hue = if whiteness + blackness >= 1.0 - 1e-9 { f64::NAN } else { hue };
+
+    [hue, whiteness * 100.0, blackness * 100.0]
+}
+
+// whiteness and blackness from 0 to 100
+fn hwb_to_srgb([hue, whiteness, blackness]: [f64; 3]) -> [f64; 3] {
+    let (whiteness, blackness) = (whiteness / 100.0, blackness / 100.0);
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return [gray; 3];
+    }
+
+    hsl_to_srgb([hue, 100.0, 50.0]).map(|c| c * (1.0 - whiteness - blackness) + whiteness)
+}
+
+fn lab_to_xyz_d50([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    let f1 = (lightness + 16.0) / 116.0;
+    let f0 = a / 500.0 + f1;
+    let f2 = f1 - b / 200.0;
+
+    let inverse = |f: f64| match f * f * f {
+        cube if cube > LAB_EPSILON => cube,
+        _ => (116.0 * f - 16.0) / LAB_KAPPA,
+    };
+    let y = match lightness {
+        l if l > LAB_KAPPA * LAB_EPSILON => f1 * f1 * f1,
+        l => l / LAB_KAPPA,
+    };
+
+    [inverse(f0) * D50_WHITE[0], y * D50_WHITE[1], inverse(f2) * D50_WHITE[2]]
+}
+
+fn xyz_d50_to_lab(xyz: [f64; 3]) -> [f64; 3] {
+    let [f0, f1, f2] = [0, 1, 2].map(|index| match xyz[index] / D50_WHITE[index] {
+        value if value > LAB_EPSILON => value.cbrt(),
+        value => (LAB_KAPPA * value + 16.0) / 116.0,
+    });
+
+    [116.0 * f1 - 16.0, 500.0 * (f0 - f1), 200.0 * (f1 - f2)]
+}
+
+// `lab` to `lch` and `oklab` to `oklch`, the hue is powerless when the chroma is below `epsilon`
+fn rectangular_to_polar([lightness, a, b]: [f64; 3], epsilon: f64) -> [f64; 3] {
+    let chroma = a.hypot(b);
+    let hue = match chroma {
+        chroma if chroma < epsilon => f64::NAN,
+        _ => b.atan2(a).to_degrees().rem_euclid(360.0),
+    };
+
+    [lightness, chroma, hue]
+}
+
+// `lch` to `lab` and `oklch` to `oklab`, the hue is in degrees
+fn polar_to_rectangular([lightness, chroma, hue]: [f64; 3]) -> [f64; 3] {
+    let hue = hue.to_radians();
+    [lightness, chroma * hue.cos(), chroma * hue.sin()]
+}
+
+fn multiply(matrix: &Matrix, vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::convert;
+    use crate::style_sheet::{AbsoluteColor, ColorSpace, Color, HueInterpolation, Rgba};
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3], tolerance: f32) {
+        let close = actual.iter().zip(&expected).all(|(actual, expected)| (actual - expected).abs() <= tolerance);
+        assert!(close, "{:?} isn't close to {:?}", actual, expected);
+    }
+
+    fn parse(input: &str) -> AbsoluteColor {
+        match Color::parse(&crate::parse_component_values(input)) {
+            Some(Color::Absolute(color)) => color,
+            color => panic!("{} parsed as {:?}", input, color),
+        }
+    }
+
+    // reference values from the CSS Color 4 sample code
+    #[test]
+    fn converts_srgb_red() {
+        let red = [1.0, 0.0, 0.0];
+        assert_close(convert(ColorSpace::Srgb, red, ColorSpace::Lab), [54.2905, 80.8049, 69.891], 0.01);
+        assert_close(convert(ColorSpace::Srgb, red, ColorSpace::Lch), [54.2905, 106.8372, 40.8526], 0.01);
+        assert_close(convert(ColorSpace::Srgb, red, ColorSpace::Oklab), [0.62796, 0.22486, 0.12585], 0.0001);
+        assert_close(convert(ColorSpace::Srgb, red, ColorSpace::Oklch), [0.62796, 0.25768, 29.2339], 0.001);
+        assert_close(convert(ColorSpace::Srgb, red, ColorSpace::DisplayP3), [0.91749, 0.20029, 0.13856], 0.0001);
+        assert_close(convert(ColorSpace::Srgb, [1.0, 1.0, 1.0], ColorSpace::XyzD65), [0.95046, 1.0, 1.08906], 0.0001);
+        assert_close(convert(ColorSpace::Srgb, [1.0, 1.0, 1.0], ColorSpace::XyzD50), [0.9643, 1.0, 0.8251], 0.0001);
+    }
+
+    #[test]
+    fn converts_cylindrical_spaces() {
+        assert_close(convert(ColorSpace::Hsl, [120.0, 100.0, 25.0], ColorSpace::Srgb), [0.0, 0.5, 0.0], 0.0001);
+        assert_close(convert(ColorSpace::Hwb, [0.0, 20.0, 20.0], ColorSpace::Srgb), [0.8, 0.2, 0.2], 0.0001);
+        assert_close(convert(ColorSpace::Srgb, [0.8, 0.2, 0.2], ColorSpace::Hsl), [0.0, 60.0, 50.0], 0.001);
+    }
+
+    #[test]
+    fn round_trips_through_every_space() {
+        let color = [0.2, 0.4, 0.6];
+        for space in [
+            ColorSpace::SrgbLinear, ColorSpace::DisplayP3, ColorSpace::A98Rgb, ColorSpace::ProphotoRgb,
+            ColorSpace::Rec2020, ColorSpace::XyzD50, ColorSpace::XyzD65, ColorSpace::Lab, ColorSpace::Lch,
+            ColorSpace::Oklab, ColorSpace::Oklch, ColorSpace::Hsl, ColorSpace::Hwb,
+        ] {
+            let converted = convert(ColorSpace::Srgb, color, space);
+            assert_close(convert(space, converted, ColorSpace::Srgb), color, 0.0001);
+        }
+    }
+
+    #[test]
+    fn achromatic_hues_are_powerless() {
+        let white = AbsoluteColor::new(ColorSpace::Srgb, [1.0, 1.0, 1.0], 1.0);
+        assert_eq!(white.to_space(ColorSpace::Oklch).components[2], None);
+        assert_eq!(white.to_space(ColorSpace::Hsl).components[0], None);
+    }
+
+    #[test]
+    fn maps_into_gamut_keeping_lightness_and_hue() {
+        let p3_red = AbsoluteColor::new(ColorSpace::DisplayP3, [1.0, 0.0, 0.0], 1.0);
+        assert!(!p3_red.is_in_gamut(ColorSpace::Srgb));
+
+        let mapped = p3_red.map_to_gamut(ColorSpace::Srgb);
+        assert!(mapped.is_in_gamut(ColorSpace::Srgb));
+        let [original, mapped] = [p3_red, mapped].map(|color| color.to_space(ColorSpace::Oklch).values());
+        assert!((original[0] - mapped[0]).abs() < 0.02, "{:?} {:?}", original, mapped);
+        assert!((original[2] - mapped[2]).abs() < 3.0, "{:?} {:?}", original, mapped);
+        assert!(mapped[1] < original[1]);
+
+        // too light to have any chroma, becomes white
+        let light = AbsoluteColor::new(ColorSpace::Oklch, [1.2, 0.1, 120.0], 1.0);
+        assert_eq!(light.to_rgba(), Rgba { red: 255, green: 255, blue: 255, alpha: 1.0 });
+    }
+
+    #[test]
+    fn mixes_colors() {
+        assert_close(parse("color-mix(in oklab, red, blue)").values(), [0.53998, 0.09591, -0.09285], 0.0005);
+        assert_close(parse("color-mix(in srgb, red 25%, blue)").values(), [0.25, 0.0, 0.75], 0.0001);
+
+        let (first, second) = (parse("hsl(10 50% 50%)"), parse("hsl(350 50% 50%)"));
+        let hue = |interpolation| first.mix(&second, 0.5, ColorSpace::Hsl, interpolation).values()[0];
+        assert!(hue(HueInterpolation::Shorter) % 360.0 < 0.001);
+        assert!((hue(HueInterpolation::Longer) - 180.0).abs() < 0.001);
+        assert!((hue(HueInterpolation::Increasing) - 180.0).abs() < 0.001);
+        assert!(hue(HueInterpolation::Decreasing) % 360.0 < 0.001);
+    }
+
+    #[test]
+    fn missing_components_take_the_other_colors() {
+        assert_close(parse("color-mix(in srgb, rgb(none 0 0), rgb(255 0 0))").values(), [1.0, 0.0, 0.0], 0.0001);
+        assert_close(parse("color-mix(in oklch, oklch(0.5 0 none), oklch(0.7 0.1 90))").values(), [0.6, 0.05, 90.0], 0.0001);
+    }
+
+    #[test]
+    fn resolves_relative_colors() {
+        assert_close(parse("rgb(from red r 0 b)").values(), [1.0, 0.0, 0.0], 0.002);
+        assert_close(parse("hsl(from rgb(0 128 0) 240 s l)").to_srgb(), [0.0, 0.0, 0.502], 0.002);
+        assert_close(parse("oklch(from red l c h)").to_srgb(), [1.0, 0.0, 0.0], 0.0001);
+        assert_close(parse("rgb(from red calc(r / 2) g b)").values(), [0.5, 0.0, 0.0], 0.002);
+        assert_close(parse("hsl(from rgb(0 128 0) calc(h + 120) s l)").to_srgb(), [0.0, 0.0, 0.502], 0.002);
+    }
+}
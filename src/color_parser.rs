@@ -0,0 +1,700 @@
+use crate::lexer::NumericType;
+use crate::style_sheet::{
+    ComponentValue, Color, AbsoluteColor, ColorSpace, AngleUnit, ColorMix, RelativeColor, HueInterpolation, CalcNode,
+    Number,
+};
+use crate::value_parser::trim_whitespace;
+use crate::calc_parser;
+
+// A parsed component, `None` for `none`
+type Component = Option<f32>;
+
+// `<color>` from CSS Color 4, `None` if the value isn't a color
+pub fn parse_color(values: &[ComponentValue]) -> Option<Color> {
+    match trim_whitespace(values) {
+        [ComponentValue::Hash(value, _)] => parse_hex(value).map(Color::Absolute),
+        [ComponentValue::Identifier(name)] => parse_named(name),
+        [ComponentValue::Function(name, arguments)] => parse_function(name, arguments),
+        _ => None,
+    }
+}
+
+fn parse_hex(value: &str) -> Option<AbsoluteColor> {
+    let digits = value.chars().map(|c| c.to_digit(16)).collect::<Option<Vec<_>>>()?;
+    let channels = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit * 17).collect::<Vec<_>>(),
+        6 | 8 => digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect(),
+        _ => return None,
+    };
+
+    // the alpha is optional
+    let channel = |index: usize| channels.get(index).map_or(1.0, |&channel| channel as f32 / 255.0);
+    Some(AbsoluteColor::rgb([channel(0), channel(1), channel(2)], channel(3)))
+}
+
+fn parse_named(name: &str) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        "currentcolor" => return Some(Color::CurrentColor),
+        "transparent" => return Some(Color::Absolute(AbsoluteColor::rgb([0.0; 3], 0.0))),
+        _ => (),
+    }
+
+    let lookup = |colors: &[(&str, u32)]| {
+        colors.binary_search_by(|(color, _)| (*color).cmp(name.as_str())).ok().map(|index| colors[index].1)
+    };
+    let rgb = lookup(NAMED_COLORS).or_else(|| lookup(SYSTEM_COLORS))?;
+    let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.0;
+
+    Some(Color::Absolute(AbsoluteColor::rgb([channel(16), channel(8), channel(0)], 1.0)))
+}
+
+fn parse_function(name: &str, arguments: &[ComponentValue]) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    if name == "color-mix" {
+        return parse_color_mix(arguments);
+    }
+
+    // relative colors, `<function>(from <color> ...)`
+    let (origin, arguments) = match trim_whitespace(arguments) {
+        [ComponentValue::Identifier(from), rest @ ..] if from.eq_ignore_ascii_case("from") => {
+            match trim_whitespace(rest).split_first() {
+                Some((origin, arguments)) => (parse_color(std::slice::from_ref(origin))?, trim_whitespace(arguments)),
+                None => return None,
+            }
+        },
+        arguments => return parse_absolute(&name, arguments, None).map(Color::Absolute),
+    };
+
+    match origin {
+        Color::Absolute(origin) => parse_relative(&name, &origin, arguments).map(Color::Absolute),
+        // checked against a stand-in for now, the origin depends on `currentcolor`
+        origin => {
+            parse_relative(&name, &AbsoluteColor::new(ColorSpace::Srgb, [0.0; 3], 1.0), arguments)?;
+            Some(Color::Relative(Box::new(RelativeColor { function: name, origin, arguments: arguments.to_vec() })))
+        },
+    }
+}
+
+// the arguments of a relative color after its origin, `function` is lowercase
+pub fn parse_relative(function: &str, origin: &AbsoluteColor, arguments: &[ComponentValue]) -> Option<AbsoluteColor> {
+    parse_absolute(function, arguments, Some(origin))
+}
+
+fn parse_absolute(function: &str, arguments: &[ComponentValue], origin: Option<&AbsoluteColor>) -> Option<AbsoluteColor> {
+    let mut arguments = split_arguments(arguments)?;
+    if arguments.legacy && (origin.is_some() || !matches!(function, "rgb" | "rgba" | "hsl" | "hsla")) {
+        return None;
+    }
+
+    let space = match function {
+        "rgb" | "rgba" => ColorSpace::Srgb,
+        "hsl" | "hsla" => ColorSpace::Hsl,
+        "color" => match arguments.components.first() {
+            Some(ComponentValue::Identifier(space)) => {
+                let space = ColorSpace::from_name(space)?;
+                arguments.components.remove(0);
+                space
+            },
+            _ => return None,
+        },
+        function => ColorSpace::from_name(function)?,
+    };
+
+    if let Some(origin) = origin {
+        let origin = origin.to_space(space);
+        // `rgb()` channels go up to 255
+        let scale = if function.starts_with("rgb") { 255.0 } else { 1.0 };
+        arguments.channels = Some(Channels {
+            names: channel_names(space),
+            values: origin.components.map(|component| component.map(|component| component * scale)),
+            alpha: origin.alpha,
+        });
+    }
+
+    match function {
+        "rgb" | "rgba" => parse_rgb(&arguments),
+        "hsl" | "hsla" => parse_hsl(&arguments),
+        "hwb" => parse_hwb(&arguments),
+        "lab" => parse_lab(&arguments, ColorSpace::Lab, 100.0, 125.0),
+        "oklab" => parse_lab(&arguments, ColorSpace::Oklab, 1.0, 0.4),
+        "lch" => parse_lch(&arguments, ColorSpace::Lch, 100.0, 150.0),
+        "oklch" => parse_lch(&arguments, ColorSpace::Oklch, 1.0, 0.4),
+        "color" => parse_color_function(&arguments, space),
+        _ => None,
+    }
+}
+
+// the keywords relative colors refer to the origin's components with, besides `alpha`
+fn channel_names(space: ColorSpace) -> [&'static str; 3] {
+    match space {
+        ColorSpace::Hsl => ["h", "s", "l"],
+        ColorSpace::Hwb => ["h", "w", "b"],
+        ColorSpace::Lab | ColorSpace::Oklab => ["l", "a", "b"],
+        ColorSpace::Lch | ColorSpace::Oklch => ["l", "c", "h"],
+        ColorSpace::XyzD50 | ColorSpace::XyzD65 => ["x", "y", "z"],
+        _ => ["r", "g", "b"],
+    }
+}
+
+// `color-mix(in <space> <hue> hue, <color> <percentage>, <color> <percentage>)`, the space defaults
+// to oklab and the percentages can come before or after their color
+fn parse_color_mix(arguments: &[ComponentValue]) -> Option<Color> {
+    let arguments = arguments.split(|value| *value == ComponentValue::Comma)
+        .map(|argument| argument.iter().filter(|value| !value.is_whitespace()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let (method, colors) = match arguments.len() {
+        2 => (None, &arguments[..]),
+        3 => (Some(&arguments[0]), &arguments[1..]),
+        _ => return None,
+    };
+
+    let (space, hue_interpolation) = match method.map(|method| method.as_slice()) {
+        None => (ColorSpace::Oklab, HueInterpolation::Shorter),
+        Some([ComponentValue::Identifier(keyword), ComponentValue::Identifier(space), hue @ ..])
+            if keyword.eq_ignore_ascii_case("in") => {
+            let space = ColorSpace::from_name(space)?;
+            let polar = matches!(space, ColorSpace::Hsl | ColorSpace::Hwb | ColorSpace::Lch | ColorSpace::Oklch);
+            let hue_interpolation = match hue {
+                [] => HueInterpolation::Shorter,
+                [ComponentValue::Identifier(method), ComponentValue::Identifier(hue)]
+                    if polar && hue.eq_ignore_ascii_case("hue") => HueInterpolation::from_name(method)?,
+                _ => return None,
+            };
+            (space, hue_interpolation)
+        },
+        _ => return None,
+    };
+
+    let parse_argument = |argument: &[&ComponentValue]| -> Option<(Color, Option<f32>)> {
+        let (color, percentage) = match argument {
+            [color] => (*color, None),
+            [ComponentValue::Percentage(percentage), color] | [color, ComponentValue::Percentage(percentage)] => {
+                (*color, Some(percentage.value as f32))
+            },
+            _ => return None,
+        };
+        if percentage.is_some_and(|percentage| !(0.0..=100.0).contains(&percentage)) {
+            return None;
+        }
+
+        Some((parse_color(std::slice::from_ref(color))?, percentage))
+    };
+    let first = parse_argument(&colors[0])?;
+    let second = parse_argument(&colors[1])?;
+    if first.1.unwrap_or(1.0) + second.1.unwrap_or(1.0) == 0.0 {
+        return None;
+    }
+
+    let mix = ColorMix { space, hue_interpolation, colors: [first, second] };
+    match &mix.colors {
+        [(Color::Absolute(_), _), (Color::Absolute(_), _)] => {
+            // there's no `currentcolor` to resolve
+            Some(Color::Absolute(mix.resolve(&AbsoluteColor::new(ColorSpace::Srgb, [0.0; 3], 1.0))))
+        },
+        _ => Some(Color::Mix(Box::new(mix))),
+    }
+}
+
+// The arguments of a color function, `a b c / alpha` or `a, b, c, alpha` in the legacy syntax
+// of `rgb()` and `hsl()`
+struct Arguments<'a> {
+    components: Vec<&'a ComponentValue>,
+    alpha: Option<&'a ComponentValue>,
+    legacy: bool,
+    // the origin's components in a relative color
+    channels: Option<Channels>,
+}
+
+struct Channels {
+    names: [&'static str; 3],
+    values: [Component; 3],
+    alpha: Component,
+}
+
+impl<'a> Arguments<'a> {
+    // clamped, opaque when left out
+    fn alpha(&self) -> Option<Component> {
+        match self.alpha {
+            Some(alpha) => self.number_or_percentage(alpha, 1.0).map(|alpha| alpha.map(|alpha| alpha.clamp(0.0, 1.0))),
+            // a relative color keeps the origin's
+            None => match &self.channels {
+                Some(channels) => Some(channels.alpha),
+                None => Some(Some(1.0)),
+            },
+        }
+    }
+
+    // the legacy syntax has no `none`
+    fn has_none(&self) -> bool {
+        self.components.iter().chain(&self.alpha).any(|value| matches!(value, ComponentValue::Identifier(..)))
+    }
+
+    fn number_or_percentage(&self, value: &ComponentValue, percentage: f32) -> Option<Component> {
+        self.channel(value).or_else(|| parse_number_or_percentage(value, percentage)).or_else(|| {
+            match self.calculation(value)? {
+                CalcNode::Number(number) => Some(Some(number)),
+                CalcNode::Percentage(number) => Some(Some(number / 100.0 * percentage)),
+                _ => None,
+            }
+        })
+    }
+
+    fn hue(&self, value: &ComponentValue) -> Option<Component> {
+        self.channel(value).or_else(|| parse_hue(value)).or_else(|| match self.calculation(value)? {
+            CalcNode::Number(degrees) => Some(Some(degrees)),
+            CalcNode::Angle(angle, unit) => Some(Some(angle * unit.to_degrees())),
+            _ => None,
+        })
+    }
+
+    // A math function that simplifies down to a single value, eg `calc(l + 0.1)` where the channel
+    // keywords of a relative color stand for numbers
+    fn calculation(&self, value: &ComponentValue) -> Option<CalcNode> {
+        match value {
+            ComponentValue::Function(name, _) if calc_parser::is_math_function(name) => {
+                CalcNode::parse(&[self.substitute_channels(value)]).filter(CalcNode::is_leaf)
+            },
+            _ => None,
+        }
+    }
+
+    fn substitute_channels(&self, value: &ComponentValue) -> ComponentValue {
+        match value {
+            ComponentValue::Identifier(..) => match self.channel(value) {
+                Some(Some(channel)) => ComponentValue::Number(Number { value: channel as f64, kind: NumericType::Number }),
+                _ => value.clone(),
+            },
+            ComponentValue::Function(name, arguments) => ComponentValue::Function(
+                name.clone(),
+                arguments.iter().map(|argument| self.substitute_channels(argument)).collect(),
+            ),
+            ComponentValue::Block(kind, values) => {
+                ComponentValue::Block(*kind, values.iter().map(|value| self.substitute_channels(value)).collect())
+            },
+            value => value.clone(),
+        }
+    }
+
+    // a channel keyword of a relative color, missing components are zero
+    fn channel(&self, value: &ComponentValue) -> Option<Component> {
+        let (channels, name) = match (&self.channels, value) {
+            (Some(channels), ComponentValue::Identifier(name)) => (channels, name),
+            _ => return None,
+        };
+
+        let value = match channels.names.iter().position(|channel| name.eq_ignore_ascii_case(channel)) {
+            Some(index) => channels.values[index],
+            None if name.eq_ignore_ascii_case("alpha") => channels.alpha,
+            None => return None,
+        };
+        Some(Some(value.unwrap_or(0.0)))
+    }
+}
+
+fn split_arguments(arguments: &[ComponentValue]) -> Option<Arguments<'_>> {
+    let values = arguments.iter().filter(|value| !value.is_whitespace()).collect::<Vec<_>>();
+
+    if values.contains(&&ComponentValue::Comma) {
+        // a comma between every argument
+        if values.len() % 2 == 0 {
+            return None;
+        }
+        let mut components = vec![];
+        for (index, value) in values.iter().enumerate() {
+            match (index % 2, value) {
+                (0, ComponentValue::Comma) => return None,
+                (0, value) => components.push(*value),
+                (_, ComponentValue::Comma) => (),
+                _ => return None,
+            }
+        }
+
+        let alpha = if components.len() == 4 { components.pop() } else { None };
+        return Some(Arguments { components, alpha, legacy: true, channels: None });
+    }
+
+    match values.iter().position(|value| **value == ComponentValue::Delim('/')) {
+        Some(slash) if slash + 2 == values.len() => Some(Arguments {
+            components: values[..slash].to_vec(),
+            alpha: Some(values[slash + 1]),
+            legacy: false,
+            channels: None,
+        }),
+        Some(_) => None,
+        None => Some(Arguments { components: values, alpha: None, legacy: false, channels: None }),
+    }
+}
+
+// `rgb(255 0 0)`, `rgb(100% 0% 0% / 50%)` or `rgba(255, 0, 0, 0.5)`
+fn parse_rgb(arguments: &Arguments) -> Option<AbsoluteColor> {
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    // the legacy syntax doesn't mix numbers and percentages
+    if arguments.legacy {
+        let percentages = matches!(arguments.components[0], ComponentValue::Percentage(..));
+        let consistent = arguments.components.iter().all(|value| match value {
+            ComponentValue::Percentage(..) => percentages,
+            ComponentValue::Number(..) => !percentages,
+            _ => false,
+        });
+        if !consistent || arguments.has_none() {
+            return None;
+        }
+    }
+
+    // out of range channels are clamped, except in relative colors which serialize as `color(srgb)`
+    let legacy = arguments.channels.is_none();
+    let channel = |value: &ComponentValue| {
+        arguments.number_or_percentage(value, 255.0).map(|channel| channel.map(|channel| {
+            let channel = channel / 255.0;
+            if legacy { channel.clamp(0.0, 1.0) } else { channel }
+        }))
+    };
+
+    Some(AbsoluteColor {
+        space: ColorSpace::Srgb,
+        components: [
+            channel(arguments.components[0])?,
+            channel(arguments.components[1])?,
+            channel(arguments.components[2])?,
+        ],
+        alpha: arguments.alpha()?,
+        legacy,
+    })
+}
+
+// `hsl(120deg 100% 50%)` or `hsla(120, 100%, 50%, 0.5)`
+fn parse_hsl(arguments: &Arguments) -> Option<AbsoluteColor> {
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    // the legacy syntax needs percentages for saturation and lightness
+    let percentages = arguments.components[1..].iter().all(|value| matches!(value, ComponentValue::Percentage(..)));
+    if arguments.legacy && (!percentages || arguments.has_none()) {
+        return None;
+    }
+
+    // out of range saturation and lightness are clamped, relative colors only clamp negative saturation
+    let legacy = arguments.channels.is_none();
+    let (min_lightness, max) = if legacy { (0.0, 100.0) } else { (f32::NEG_INFINITY, f32::INFINITY) };
+
+    Some(AbsoluteColor {
+        space: ColorSpace::Hsl,
+        components: [
+            arguments.hue(arguments.components[0])?,
+            arguments.number_or_percentage(arguments.components[1], 100.0)?.map(|saturation| saturation.clamp(0.0, max)),
+            arguments.number_or_percentage(arguments.components[2], 100.0)?.map(|lightness| lightness.clamp(min_lightness, max)),
+        ],
+        alpha: arguments.alpha()?,
+        legacy,
+    })
+}
+
+// `hwb(120deg 20% 30%)`
+fn parse_hwb(arguments: &Arguments) -> Option<AbsoluteColor> {
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    Some(AbsoluteColor {
+        space: ColorSpace::Hwb,
+        components: [
+            arguments.hue(arguments.components[0])?,
+            arguments.number_or_percentage(arguments.components[1], 100.0)?,
+            arguments.number_or_percentage(arguments.components[2], 100.0)?,
+        ],
+        alpha: arguments.alpha()?,
+        legacy: arguments.channels.is_none(),
+    })
+}
+
+// `lab()` and `oklab()`, percentages are relative to the largest lightness and a/b values
+fn parse_lab(arguments: &Arguments, space: ColorSpace, lightness: f32, ab: f32) -> Option<AbsoluteColor> {
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    Some(AbsoluteColor {
+        space,
+        components: [
+            arguments.number_or_percentage(arguments.components[0], lightness)?.map(|l| l.clamp(0.0, lightness)),
+            arguments.number_or_percentage(arguments.components[1], ab)?,
+            arguments.number_or_percentage(arguments.components[2], ab)?,
+        ],
+        alpha: arguments.alpha()?,
+        legacy: false,
+    })
+}
+
+// `lch()` and `oklch()`
+fn parse_lch(arguments: &Arguments, space: ColorSpace, lightness: f32, chroma: f32) -> Option<AbsoluteColor> {
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    Some(AbsoluteColor {
+        space,
+        components: [
+            arguments.number_or_percentage(arguments.components[0], lightness)?.map(|l| l.clamp(0.0, lightness)),
+            arguments.number_or_percentage(arguments.components[1], chroma)?.map(|c| c.max(0.0)),
+            arguments.hue(arguments.components[2])?,
+        ],
+        alpha: arguments.alpha()?,
+        legacy: false,
+    })
+}
+
+// `color(display-p3 1 0.5 0 / 50%)`, percentages are relative to 1 in every space
+fn parse_color_function(arguments: &Arguments, space: ColorSpace) -> Option<AbsoluteColor> {
+    match space {
+        ColorSpace::Srgb | ColorSpace::SrgbLinear | ColorSpace::DisplayP3 | ColorSpace::A98Rgb
+        | ColorSpace::ProphotoRgb | ColorSpace::Rec2020 | ColorSpace::XyzD50 | ColorSpace::XyzD65 => (),
+        _ => return None,
+    }
+    if arguments.components.len() != 3 {
+        return None;
+    }
+
+    Some(AbsoluteColor {
+        space,
+        components: [
+            arguments.number_or_percentage(arguments.components[0], 1.0)?,
+            arguments.number_or_percentage(arguments.components[1], 1.0)?,
+            arguments.number_or_percentage(arguments.components[2], 1.0)?,
+        ],
+        alpha: arguments.alpha()?,
+        legacy: false,
+    })
+}
+
+// a number, or a percentage of `percentage`
+fn parse_number_or_percentage(value: &ComponentValue, percentage: f32) -> Option<Component> {
+    match value {
+        ComponentValue::Number(number) => Some(Some(number.value as f32)),
+        ComponentValue::Percentage(number) => Some(Some(number.value as f32 / 100.0 * percentage)),
+        ComponentValue::Identifier(name) if name.eq_ignore_ascii_case("none") => Some(None),
+        _ => None,
+    }
+}
+
+// in degrees, which a plain number is in too
+fn parse_hue(value: &ComponentValue) -> Option<Component> {
+    match value {
+        ComponentValue::Number(number) => Some(Some(number.value as f32)),
+        ComponentValue::Dimension(number, unit) => {
+            AngleUnit::from_name(unit).map(|unit| Some(number.value as f32 * unit.to_degrees()))
+        },
+        ComponentValue::Identifier(name) if name.eq_ignore_ascii_case("none") => Some(None),
+        _ => None,
+    }
+}
+
+// sorted for binary search
+static NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+// system colors resolve to their usual values in a light color scheme
+static SYSTEM_COLORS: &[(&str, u32)] = &[
+    ("accentcolor", 0x0075ff),
+    ("accentcolortext", 0xffffff),
+    ("activetext", 0xff0000),
+    ("buttonborder", 0x767676),
+    ("buttonface", 0xefefef),
+    ("buttontext", 0x000000),
+    ("canvas", 0xffffff),
+    ("canvastext", 0x000000),
+    ("field", 0xffffff),
+    ("fieldtext", 0x000000),
+    ("graytext", 0x808080),
+    ("highlight", 0xb5d5ff),
+    ("highlighttext", 0x000000),
+    ("linktext", 0x0000ee),
+    ("mark", 0xffff00),
+    ("marktext", 0x000000),
+    ("selecteditem", 0x0075ff),
+    ("selecteditemtext", 0xffffff),
+    ("visitedtext", 0x551a8b),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::style_sheet::{AbsoluteColor, Color, Rgba};
+
+    fn parse(input: &str) -> AbsoluteColor {
+        match Color::parse(&crate::parse_component_values(input)) {
+            Some(Color::Absolute(color)) => color,
+            color => panic!("{} parsed as {:?}", input, color),
+        }
+    }
+
+    #[test]
+    fn clamps_legacy_channels() {
+        let red = Rgba { red: 255, green: 0, blue: 0, alpha: 1.0 };
+        assert_eq!(parse("rgb(300 0 0)").to_rgba(), red);
+        assert_eq!(parse("rgb(300 0 0)").to_string(), "rgb(255, 0, 0)");
+        assert_eq!(parse("rgba(100%, -10%, 0%, 1)").to_rgba(), red);
+        assert_eq!(parse("hsl(0 150% 50%)").to_rgba(), red);
+        assert_eq!(parse("hsl(0 -20% 120%)").to_rgba(), Rgba { red: 255, green: 255, blue: 255, alpha: 1.0 });
+    }
+
+    #[test]
+    fn serializes_color_function_srgb_unclamped() {
+        assert_eq!(parse("color(srgb 0.5 0.5 0.5)").to_string(), "color(srgb 0.5 0.5 0.5)");
+        assert_eq!(parse("color(srgb 1.5 0 0 / 50%)").to_string(), "color(srgb 1.5 0 0 / 0.5)");
+        assert_eq!(parse("#808080").to_string(), "rgb(128, 128, 128)");
+        assert_eq!(parse("hsl(120 100% 25%)").to_string(), "rgb(0, 128, 0)");
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use crate::style_sheet::ComponentValue;
+use crate::value_parser::trim_whitespace;
+
+// Substitutes the `var()`s in the custom properties of an element, whose values are the raw
+// component values they were declared with. The ones that depend on each other in a cycle, or
+// on a property that doesn't exist without a fallback, end up with the guaranteed-invalid value,
+// which is the same as not being there. Inherited custom properties are expected to be
+// substituted already, by the parent.
+pub fn resolve(values: &mut HashMap<String, Vec<ComponentValue>>) {
+    let names = values.keys().filter(|name| name.starts_with("--")).cloned().collect::<Vec<_>>();
+    let mut graph = DependencyGraph {
+        dependencies: names.iter()
+            .map(|name| {
+                let mut dependencies = vec![];
+                collect_references(&values[name], &mut dependencies);
+                dependencies.retain(|dependency| values.contains_key(dependency));
+                (name.clone(), dependencies)
+            })
+            .collect(),
+        indices: HashMap::new(),
+        stack: vec![],
+        components: vec![],
+    };
+    for name in &names {
+        if !graph.indices.contains_key(name) {
+            graph.visit(name);
+        }
+    }
+
+    // the components come out with their dependencies first
+    for component in graph.components {
+        let cyclic = component.len() > 1 || graph.dependencies[&component[0]].contains(&component[0]);
+        for name in component {
+            let substituted = if cyclic { None } else { substitute(&values[&name], values) };
+            match substituted {
+                Some(value) => values.insert(name, value),
+                None => values.remove(&name),
+            };
+        }
+    }
+}
+
+// `None` for the guaranteed-invalid value, when a `var()` refers to a custom property that isn't
+// in `custom_properties` and has no fallback, or isn't a valid `var()` at all
+pub fn substitute(values: &[ComponentValue], custom_properties: &HashMap<String, Vec<ComponentValue>>) -> Option<Vec<ComponentValue>> {
+    let mut substituted = vec![];
+    for value in values {
+        match value {
+            ComponentValue::Function(name, arguments) if name.eq_ignore_ascii_case("var") => {
+                let (name, fallback) = parse_var(arguments)?;
+                match (custom_properties.get(name), fallback) {
+                    (Some(value), _) => substituted.extend(value.iter().cloned()),
+                    (None, Some(fallback)) => substituted.extend(substitute(fallback, custom_properties)?),
+                    (None, None) => return None,
+                }
+            },
+            ComponentValue::Function(name, arguments) => {
+                substituted.push(ComponentValue::Function(name.clone(), substitute(arguments, custom_properties)?));
+            },
+            ComponentValue::Block(kind, values) => {
+                substituted.push(ComponentValue::Block(*kind, substitute(values, custom_properties)?));
+            },
+            value => substituted.push(value.clone()),
+        }
+    }
+
+    Some(substituted)
+}
+
+pub fn contains_var(values: &[ComponentValue]) -> bool {
+    values.iter().any(|value| match value {
+        ComponentValue::Function(name, arguments) => name.eq_ignore_ascii_case("var") || contains_var(arguments),
+        ComponentValue::Block(_, values) => contains_var(values),
+        _ => false,
+    })
+}
+
+// `var(--name)` or `var(--name, fallback)`, the fallback may be empty
+fn parse_var(arguments: &[ComponentValue]) -> Option<(&str, Option<&[ComponentValue]>)> {
+    let (name, fallback) = match arguments.iter().position(|value| *value == ComponentValue::Comma) {
+        Some(comma) => (&arguments[..comma], Some(trim_whitespace(&arguments[comma + 1..]))),
+        None => (arguments, None),
+    };
+
+    match trim_whitespace(name) {
+        [ComponentValue::Identifier(name)] if name.starts_with("--") => Some((name, fallback)),
+        _ => None,
+    }
+}
+
+// the names in every `var()`, fallbacks included
+fn collect_references(values: &[ComponentValue], references: &mut Vec<String>) {
+    for value in values {
+        match value {
+            ComponentValue::Function(name, arguments) => {
+                if name.eq_ignore_ascii_case("var") {
+                    if let Some((name, _)) = parse_var(arguments) {
+                        references.push(name.to_string());
+                    }
+                }
+                collect_references(arguments, references);
+            },
+            ComponentValue::Block(_, values) => collect_references(values, references),
+            _ => (),
+        }
+    }
+}
+
+// Tarjan's strongly connected components, every component with more than one property, or one
+// that refers to itself, is a cycle
+struct DependencyGraph {
+    dependencies: HashMap<String, Vec<String>>,
+    // the order the properties were visited in
+    indices: HashMap<String, usize>,
+    stack: Vec<String>,
+    components: Vec<Vec<String>>,
+}
+
+impl DependencyGraph {
+    // the lowest index reachable from `name` through properties still on the stack
+    fn visit(&mut self, name: &str) -> usize {
+        let index = self.indices.len();
+        self.indices.insert(name.to_string(), index);
+        self.stack.push(name.to_string());
+
+        let mut low_link = index;
+        for dependency in self.dependencies[name].clone() {
+            let dependency_link = match self.indices.get(&dependency) {
+                None => self.visit(&dependency),
+                Some(&dependency_index) if self.stack.contains(&dependency) => dependency_index,
+                Some(_) => continue,
+            };
+            low_link = low_link.min(dependency_link);
+        }
+
+        if low_link == index {
+            let start = self.stack.iter().rposition(|other| other == name).unwrap_or(0);
+            self.components.push(self.stack.split_off(start));
+        }
+        low_link
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{resolve, substitute};
+    use crate::style_sheet::ComponentValue;
+
+    fn serialize(values: &[ComponentValue]) -> String {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    // the resolved custom properties, by name
+    fn resolved(declarations: &[(&str, &str)]) -> HashMap<String, String> {
+        let mut values = declarations.iter()
+            .map(|(name, value)| (name.to_string(), crate::parse_component_values(value)))
+            .collect::<HashMap<_, _>>();
+        resolve(&mut values);
+        values.into_iter().map(|(name, value)| (name, serialize(&value))).collect()
+    }
+
+    #[test]
+    fn substitutes_with_fallbacks() {
+        let values = resolved(&[
+            ("--color", "#06c"),
+            ("--gap", "4px"),
+            ("--size", "calc(var(--gap) * 2)"),
+            ("--fallback", "var(--missing, 1px var(--gap))"),
+            ("--empty-fallback", "a var(--missing,)"),
+            ("--empty", ""),
+            ("--uses-empty", "[var(--empty)]"),
+        ]);
+        assert_eq!(values["--color"], "#06c");
+        assert_eq!(values["--size"], "calc(4px * 2)");
+        assert_eq!(values["--fallback"], "1px 4px");
+        assert_eq!(values["--empty-fallback"], "a ");
+        assert_eq!(values["--uses-empty"], "[]");
+    }
+
+    #[test]
+    fn missing_references_are_invalid() {
+        let values = resolved(&[("--a", "var(--missing)"), ("--b", "var(--a, 1px)"), ("--c", "var(nope)")]);
+        assert!(!values.contains_key("--a"));
+        assert_eq!(values["--b"], "1px");
+        assert!(!values.contains_key("--c"));
+    }
+
+    #[test]
+    fn cycles_are_invalid() {
+        let values = resolved(&[
+            ("--self", "var(--self, 1px)"),
+            ("--a", "var(--b)"),
+            ("--b", "var(--c)"),
+            ("--c", "var(--a)"),
+            // in the cycle through `--a`, even though it's reached after `--a` has been visited
+            ("--d", "var(--e) var(--f)"),
+            ("--e", "var(--d)"),
+            ("--f", "var(--e)"),
+            ("--outside", "var(--a, 2px)"),
+            ("--outside-without-fallback", "var(--a)"),
+        ]);
+        for name in ["--self", "--a", "--b", "--c", "--d", "--e", "--f", "--outside-without-fallback"] {
+            assert!(!values.contains_key(name), "{} should be invalid", name);
+        }
+        assert_eq!(values["--outside"], "2px");
+    }
+
+    #[test]
+    fn names_are_case_sensitive() {
+        let values = resolved(&[("--Gap", "1px"), ("--gap", "2px"), ("--a", "var(--Gap)")]);
+        assert_eq!(values["--a"], "1px");
+    }
+
+    #[test]
+    fn substitutes_into_regular_values() {
+        let custom = resolved(&[("--x", "255")])
+            .into_iter()
+            .map(|(name, value)| (name, crate::parse_component_values(&value)))
+            .collect();
+        let substituted = substitute(&crate::parse_component_values("rgb(var(--x) 0 0) var(--y, solid)"), &custom);
+        assert_eq!(substituted.map(|values| serialize(&values)).as_deref(), Some("rgb(255 0 0) solid"));
+        assert_eq!(substitute(&crate::parse_component_values("var(--y)"), &custom), None);
+    }
+}
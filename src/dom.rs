@@ -0,0 +1,158 @@
+use std::ptr;
+
+use crate::selectors::Element;
+
+// A minimal in-memory document to match selectors against, nodes live in an arena and are
+// addressed by `NodeId`
+#[derive(Debug, Default)]
+pub struct Document {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Default)]
+pub struct Node {
+    pub tag_name: String,
+    pub namespace: String,
+    // `(namespace, name, value)`, the namespace is empty for regular attributes
+    pub attributes: Vec<(String, String, String)>,
+    pub states: Vec<String>,
+    pub text: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Document {
+    pub fn new() -> Document {
+        Document::default()
+    }
+
+    // appends a new element as the last child of `parent`, or as a root without one
+    pub fn append_element(&mut self, parent: Option<NodeId>, tag_name: &str) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            tag_name: tag_name.to_string(),
+            parent,
+            ..Node::default()
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) {
+        self.set_attribute_ns(id, "", name, value);
+    }
+
+    pub fn set_attribute_ns(&mut self, id: NodeId, namespace: &str, name: &str, value: &str) {
+        let attributes = &mut self.nodes[id.0].attributes;
+        match attributes.iter_mut().find(|(ns, n, _)| ns == namespace && n == name) {
+            Some(attribute) => attribute.2 = value.to_string(),
+            None => attributes.push((namespace.to_string(), name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn set_state(&mut self, id: NodeId, state: &str) {
+        self.nodes[id.0].states.push(state.to_string());
+    }
+
+    pub fn element(&self, id: NodeId) -> ElementRef<'_> {
+        ElementRef { document: self, id }
+    }
+}
+
+// A handle to an element of a `Document`, what selectors are matched against
+#[derive(Debug, Clone, Copy)]
+pub struct ElementRef<'a> {
+    document: &'a Document,
+    id: NodeId,
+}
+
+impl<'a> ElementRef<'a> {
+    pub fn node_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn node(&self) -> &'a Node {
+        self.document.node(self.id)
+    }
+
+    fn sibling(&self, offset: isize) -> Option<ElementRef<'a>> {
+        let parent = self.node().parent?;
+        let siblings = &self.document.node(parent).children;
+        let index = siblings.iter().position(|&id| id == self.id)? as isize + offset;
+        if index < 0 {
+            return None;
+        }
+
+        siblings.get(index as usize).map(|&id| self.document.element(id))
+    }
+}
+
+impl<'a> PartialEq for ElementRef<'a> {
+    fn eq(&self, other: &ElementRef<'a>) -> bool {
+        ptr::eq(self.document, other.document) && self.id == other.id
+    }
+}
+
+impl<'a> Element for ElementRef<'a> {
+    fn parent(&self) -> Option<Self> {
+        self.node().parent.map(|id| self.document.element(id))
+    }
+
+    fn previous_sibling(&self) -> Option<Self> {
+        self.sibling(-1)
+    }
+
+    fn next_sibling(&self) -> Option<Self> {
+        self.sibling(1)
+    }
+
+    fn first_child(&self) -> Option<Self> {
+        self.node().children.first().map(|&id| self.document.element(id))
+    }
+
+    fn tag_name(&self) -> &str {
+        &self.node().tag_name
+    }
+
+    fn namespace(&self) -> &str {
+        &self.node().namespace
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.attribute(Some(""), "id")
+    }
+
+    fn has_class(&self, name: &str) -> bool {
+        self.attribute(Some(""), "class")
+            .is_some_and(|classes| classes.split_whitespace().any(|class| class == name))
+    }
+
+    fn attribute(&self, namespace: Option<&str>, name: &str) -> Option<&str> {
+        self.node().attributes.iter()
+            .find(|(ns, n, _)| namespace.map_or(true, |namespace| namespace == ns) && n == name)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    fn has_state(&self, state: &str) -> bool {
+        self.node().states.iter().any(|s| s == state)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.node().children.is_empty() && self.node().text.is_empty()
+    }
+}
@@ -1,24 +1,43 @@
 use std::fmt;
 use std::error;
 
+use crate::span::Span;
+
 #[derive(Debug, PartialOrd, PartialEq, Clone)]
 pub enum ParserError {
     // Fatal(String),
-    InvalidNumber(String),
-    UnknownToken(String),
+    InvalidNumber(String, Span),
+    UnknownToken(String, Span),
+    // a namespace prefix used in a selector without a matching `@namespace`
+    UndeclaredNamespace(String, Span),
+    // an at-rule where it isn't allowed, eg `@namespace` after a style rule
+    MisplacedAtRule(String, Span),
     UnexpectedToken {
         found: String,
         expected: Option<String>,
         context: Option<String>,
+        span: Span,
     },
 }
 
+impl ParserError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::InvalidNumber(_, span)
+            | ParserError::UnknownToken(_, span)
+            | ParserError::UndeclaredNamespace(_, span)
+            | ParserError::MisplacedAtRule(_, span)
+            | ParserError::UnexpectedToken { span, .. } => *span,
+        }
+    }
+}
+
 impl error::Error for ParserError {}
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParserError::UnexpectedToken { found, context, expected } => {
+            ParserError::UnexpectedToken { found, context, expected, span } => {
                 let context_string = match context {
                     None => String::new(),
                     Some(c) => format!(" found in {}", c),
@@ -29,11 +48,28 @@ impl fmt::Display for ParserError {
                     Some(e) => format!(", expected `{}`", e),
                 };
 
-                write!(f, "Unexpected token{} `{}`{}", context_string, found, expected_string)
+                write!(f, "Unexpected token{} `{}`{} at {}", context_string, found, expected_string, span)
             },
-            ParserError::UnknownToken(message) => write!(f, "Unknown token `{}`", message),
+            ParserError::UnknownToken(message, span) => write!(f, "Unknown token `{}` at {}", message, span),
+            ParserError::UndeclaredNamespace(prefix, span) => write!(f, "Undeclared namespace prefix `{}` at {}", prefix, span),
+            ParserError::MisplacedAtRule(name, span) => write!(f, "Misplaced at-rule `@{}` at {}", name, span),
             // ParserError::Fatal(message) => write!(f, "Fatal error {}", message),
-            ParserError::InvalidNumber(message) => write!(f, "Number parse error: '{}'", message),
+            ParserError::InvalidNumber(message, span) => write!(f, "Number parse error: '{}' at {}", message, span),
+        }
+    }
+}
+
+#[derive(Debug, PartialOrd, PartialEq, Clone)]
+pub enum ParserWarning {
+    EmptyRule(Span),
+    MissingSemicolon(String, Span),
+}
+
+impl fmt::Display for ParserWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParserWarning::EmptyRule(span) => write!(f, "Rule has no declarations at {}", span),
+            ParserWarning::MissingSemicolon(name, span) => write!(f, "Missing `;` after declaration `{}` at {}", name, span),
         }
     }
 }
@@ -42,15 +78,33 @@ impl fmt::Display for ParserError {
 pub struct ErrorHandler {
     // TODO make private
     pub errors: Vec<ParserError>,
+    pub warnings: Vec<ParserWarning>,
+    strict: bool,
 }
 
 impl ErrorHandler {
-    pub fn new() -> ErrorHandler {
+    pub fn new(strict: bool) -> ErrorHandler {
         ErrorHandler {
             errors: vec![],
+            warnings: vec![],
+            strict,
         }
     }
-    pub fn flag(&mut self, error: &ParserError) -> () {
+
+    pub fn flag(&mut self, error: &ParserError) {
+        if self.is_aborted() {
+            return;
+        }
+
         self.errors.push(error.clone());
     }
+
+    pub fn warn(&mut self, warning: ParserWarning) {
+        self.warnings.push(warning);
+    }
+
+    // in strict mode parsing stops at the first error
+    pub fn is_aborted(&self) -> bool {
+        self.strict && !self.errors.is_empty()
+    }
 }
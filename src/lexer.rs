@@ -1,231 +1,596 @@
+use std::borrow::Cow;
+use std::fmt;
+
 use crate::source::{Source};
 use crate::error::ParserError;
+use crate::span::{Position, Span};
 
-use std::fmt;
-
+// Tokens as defined by https://www.w3.org/TR/css-syntax-3/#tokenization
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
-    ClassSelector(String),
-    IdSelector(String),
-    Identifier(String),
-    String(String),
-    Integer(i32),
-    Float(f32),
+// Values borrow from the input, they're only copied when an escape has to be decoded
+pub enum Token<'a> {
+    Identifier(Cow<'a, str>),
+    Function(Cow<'a, str>),
+    AtKeyword(Cow<'a, str>),
+    Hash(Cow<'a, str>, HashType),
+    String(Cow<'a, str>),
+    BadString,
+    Url(Cow<'a, str>),
+    BadUrl,
+    Delim(char),
+    Number(Numeric<'a>),
+    Percentage(Numeric<'a>),
+    Dimension(Numeric<'a>, Cow<'a, str>),
+    Whitespace(&'a str),
+    CDO(&'a str),
+    CDC(&'a str),
     EOF,
-    Error(ParserError),
-    // Percentage(f32, String), // not supported yet
 
     // symbols
-    Dot(String),
-    Pound(String),
-    Star(String),
-    Comma(String),
-    Colon(String),
-    Semicolon(String),
-    LeftBrace(String),
-    RightBrace(String),
-    SingleQuote(String),
-    DoubleQuote(String),
-    Minus(String),
-    Whitespace(String),
+    Colon(&'a str),
+    Semicolon(&'a str),
+    Comma(&'a str),
+    LeftBracket(&'a str),
+    RightBracket(&'a str),
+    LeftParen(&'a str),
+    RightParen(&'a str),
+    LeftBrace(&'a str),
+    RightBrace(&'a str),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HashType {
+    // the hash value is a valid identifier, eg `#foo` but not `#123`
+    Id,
+    Unrestricted,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumericType {
+    Integer,
+    Number,
 }
 
-impl fmt::Display for Token {
+#[derive(Debug, PartialEq, Clone)]
+pub struct Numeric<'a> {
+    pub value: f64,
+    pub kind: NumericType,
+    // the number as written in the source, eg `+1.50` or `1e3`
+    pub representation: &'a str,
+}
+
+impl<'a> fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(self, f)
     }
 }
 
-#[derive(Debug)]
-pub struct CssLexer {
-    source: Source,
-    current: Option<Token>,
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken<'a> {
+    pub token: Token<'a>,
+    pub span: Span,
+}
+
+// Accumulates a token value, borrowing from the input until an escape forces a copy
+struct ValueBuilder {
+    start: usize,
+    end: usize,
+    owned: Option<String>,
 }
 
-// TODO return iterator
-impl CssLexer {
-    pub fn new(source: Source) -> CssLexer {
-        CssLexer { source, current: None }
+impl ValueBuilder {
+    fn new(start: usize) -> ValueBuilder {
+        ValueBuilder { start, end: start, owned: None }
     }
 
-    pub fn current_token(&mut self) -> &Token {
-        match self.current {
-            None => {
-                let token = self.extract_token();
-                self.current = Some(token);
-                self.current.as_ref().unwrap()
-            },
-            _ => self.current.as_ref().unwrap(),
+    // `c` has to be the character at the end of the value so far
+    fn push_borrowed(&mut self, c: char) {
+        match &mut self.owned {
+            Some(value) => value.push(c),
+            None => self.end += c.len_utf8(),
         }
     }
 
-    pub fn next_token(&mut self) -> &Token {
+    fn push_owned(&mut self, c: char, source: &Source) {
+        self.make_owned(source).push(c);
+    }
+
+    // called before skipping input, after that the value can't be a slice anymore
+    fn make_owned(&mut self, source: &Source) -> &mut String {
+        let (start, end) = (self.start, self.end);
+        self.owned.get_or_insert_with(|| source.slice(start, end).to_string())
+    }
+
+    fn build<'a>(self, source: &Source<'a>) -> Cow<'a, str> {
+        match self.owned {
+            Some(value) => Cow::Owned(value),
+            None => Cow::Borrowed(source.slice(self.start, self.end)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CssLexer<'a> {
+    source: Source<'a>,
+    current: Option<SpannedToken<'a>>,
+    token_start: Position,
+    // parse errors don't interrupt tokenization, they're collected here instead
+    errors: Vec<ParserError>,
+    // set once the iterator has returned EOF
+    finished: bool,
+}
+
+impl<'a> CssLexer<'a> {
+    pub fn new(source: Source<'a>) -> CssLexer<'a> {
+        CssLexer {
+            source,
+            current: None,
+            token_start: Position::start(),
+            errors: vec![],
+            finished: false,
+        }
+    }
+
+    pub fn current_token(&mut self) -> &Token<'a> {
+        &self.current_spanned_token().token
+    }
+
+    pub fn current_span(&mut self) -> Span {
+        self.current_spanned_token().span
+    }
+
+    // source text of the current token, escapes are left as written
+    pub fn current_text(&mut self) -> &'a str {
+        let span = self.current_span();
+        self.source.slice(span.start.offset, span.end.offset)
+    }
+
+    pub fn next_token(&mut self) -> &Token<'a> {
         self.current = None;
         self.current_token()
     }
 
-    fn extract_token(&mut self) -> Token {
-        let current_char = match self.skip_comments() {
+    pub fn take_errors(&mut self) -> Vec<ParserError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn current_spanned_token(&mut self) -> &SpannedToken<'a> {
+        if self.current.is_none() {
+            let token = self.extract_token();
+            let span = Span::new(self.token_start, self.source.position());
+            self.current = Some(SpannedToken { token, span });
+        }
+
+        self.current.as_ref().unwrap()
+    }
+
+    fn extract_token(&mut self) -> Token<'a> {
+        let current_char = self.skip_comments();
+        self.token_start = self.source.position();
+        let current_char = match current_char {
             None => return Token::EOF,
             Some(c) => c,
         };
 
-        if current_char.is_whitespace() || current_char == '\n' {
-            return self.extract_whitespace();
-        }
-
         match current_char {
-            '.' | '#' => self.extract_selector_or_symbol(current_char),
-            '*' | '{' | '}' | ';' | ':' | ',' => self.extract_symbol(current_char),
-            '\'' | '"' => self.extract_string(current_char),
-            '0' ... '9' => self.extract_number(),
-            'a' ... 'z' => self.extract_word(),
-            c => {
+            c if is_whitespace(c) => self.extract_whitespace(),
+            '"' | '\'' => self.extract_string(current_char),
+            '#' => match (self.source.peek_character(), self.source.peek_nth_character(2)) {
+                (Some(next), second) if is_name_part(next) || is_valid_escape(Some(next), second) => {
+                    self.source.next_character();
+                    let kind = match self.would_start_identifier() {
+                        true => HashType::Id,
+                        false => HashType::Unrestricted,
+                    };
+
+                    Token::Hash(self.extract_name(), kind)
+                },
+                _ => self.extract_delim(current_char),
+            },
+            '+' | '.' => match self.would_start_number() {
+                true => self.extract_numeric(),
+                false => self.extract_delim(current_char),
+            },
+            '-' => match (self.source.peek_character(), self.source.peek_nth_character(2)) {
+                _ if self.would_start_number() => self.extract_numeric(),
+                (Some('-'), Some('>')) => {
+                    self.source.next_character();
+                    self.source.next_character();
+                    self.source.next_character();
+                    Token::CDC(self.token_text())
+                },
+                _ if self.would_start_identifier() => self.extract_ident_like(),
+                _ => self.extract_delim(current_char),
+            },
+            '<' => match (
+                self.source.peek_character(),
+                self.source.peek_nth_character(2),
+                self.source.peek_nth_character(3),
+            ) {
+                (Some('!'), Some('-'), Some('-')) => {
+                    for _ in 0..4 {
+                        self.source.next_character();
+                    }
+                    Token::CDO(self.token_text())
+                },
+                _ => self.extract_delim(current_char),
+            },
+            '@' => {
                 self.source.next_character();
-                Token::Error(ParserError::UnexpectedToken {
-                    found: c.to_string(),
-                    context: None,
-                    expected: None,
-                })
-            }
-        }
-    }
-
-    fn extract_selector_or_symbol(&mut self, current_char: char) -> Token {
-        match self.source.peek_character() {
-            Some(next) if is_start_of_word(next) => {
-                let selector = self.extract_while(|c| !is_start_of_selector(c) && is_word_part(c));
-                match current_char {
-                    '#' => Token::IdSelector(selector),
-                    '.' => Token::ClassSelector(selector),
-                    _ => return Token::Error(ParserError::UnexpectedToken {
-                        found: current_char.to_string(),
-                        expected: Some("`#` or `.`".to_string()),
-                        context: None,
-                    }),
+                match self.would_start_identifier() {
+                    true => Token::AtKeyword(self.extract_name()),
+                    false => Token::Delim(current_char),
                 }
-                /*
-                Token1::Word(
-                    self.extract_while(|c| !is_start_of_selector(c) && is_word_part(c)),
-                    match current_char {
-                        '#' => TokenKind::IdSelector,
-                        '.' => TokenKind::ClassSelector,
-                        _ => return Token1::Error(ParserError::UnexpectedToken {
-                            found: current_char.to_string(),
-                            expected: Some("`#` or `.`".to_string()),
-                            context: None,
-                        }),
-                    },
-                )
-                */
             },
-            _ => self.extract_symbol(current_char),
+            '(' | ')' | '[' | ']' | '{' | '}' | ',' | ':' | ';' => self.extract_symbol(current_char),
+            '0'..='9' => self.extract_numeric(),
+            '\\' => match self.would_start_identifier() {
+                true => self.extract_ident_like(),
+                false => {
+                    self.flag_unexpected("\\".to_string(), None);
+                    self.extract_delim(current_char)
+                },
+            },
+            c if is_name_start(c) => self.extract_ident_like(),
+            c => self.extract_delim(c),
         }
     }
 
-    fn extract_symbol(&mut self, current_char: char) -> Token {
-        let value = current_char.to_string();
+    fn extract_delim(&mut self, current_char: char) -> Token<'a> {
         self.source.next_character();
+        Token::Delim(current_char)
+    }
+
+    fn extract_symbol(&mut self, current_char: char) -> Token<'a> {
+        self.source.next_character();
+        let value = self.token_text();
 
         match current_char {
-            '.' => Token::Dot(value),
-            '#' => Token::Pound(value),
-            '*' => Token::Star(value),
             ',' => Token::Comma(value),
             ':' => Token::Colon(value),
             ';' => Token::Semicolon(value),
+            '(' => Token::LeftParen(value),
+            ')' => Token::RightParen(value),
+            '[' => Token::LeftBracket(value),
+            ']' => Token::RightBracket(value),
             '{' => Token::LeftBrace(value),
             '}' => Token::RightBrace(value),
-            '-' => Token::Minus(value),
-            '"' => Token::DoubleQuote(value),
-            '\'' => Token::SingleQuote(value),
-            _ => return Token::Error(ParserError::UnknownToken(value)),
-        }
-        /*
-        Token1::Symbol(current_char.to_string(), match current_char {
-            '.' => TokenKind::Dot,
-            '#' => TokenKind::Pound,
-            '*' => TokenKind::Star,
-            ',' => TokenKind::Comma,
-            ':' => TokenKind::Colon,
-            ';' => TokenKind::Semicolon,
-            '{' => TokenKind::LeftBrace,
-            '}' => TokenKind::RightBrace,
-            '\'' => TokenKind::SingleQuote,
-            '"' => TokenKind::DoubleQuote,
-            '-' => TokenKind::Minus,
-            c => return Token1::Error(ParserError::UnknownToken(c.to_string())),
-        })
-        */
-    }
-
-    // TODO handle quotes in strings
-    fn extract_string(&mut self, delimiter: char) -> Token {
+            _ => unreachable!("`{}` is not a symbol", current_char),
+        }
+    }
+
+    fn extract_string(&mut self, delimiter: char) -> Token<'a> {
+        // consume the opening quote
         self.source.next_character();
-        let value = self.extract_while(|c| c != delimiter);
+        let mut value = ValueBuilder::new(self.source.position().offset);
 
-        match self.source.current_character() {
-            // TODO clean up repeated code
-            None => return Token::Error(ParserError::UnexpectedToken {
-                found: "EOF".to_string(),
-                expected: Some(delimiter.to_string()),
-                context: None,
-            }),
-            Some(found) if found != delimiter => return Token::Error(ParserError::UnexpectedToken {
-                found: found.to_string(),
-                expected: Some(delimiter.to_string()),
-                context: None,
-            }),
-            _ => {
+        loop {
+            match self.source.current_character() {
+                None => {
+                    self.flag_unexpected("EOF".to_string(), Some(&delimiter.to_string()));
+                    return Token::String(value.build(&self.source));
+                },
+                Some(c) if c == delimiter => {
+                    self.source.next_character();
+                    return Token::String(value.build(&self.source));
+                },
+                Some(c) if is_newline(c) => {
+                    // the newline isn't consumed, it becomes a whitespace token
+                    self.flag_unexpected("newline".to_string(), Some(&delimiter.to_string()));
+                    return Token::BadString;
+                },
+                Some('\\') => match self.source.peek_character() {
+                    None => {
+                        value.make_owned(&self.source);
+                        self.source.next_character();
+                    },
+                    // an escaped newline continues the string on the next line
+                    Some(next) if is_newline(next) => {
+                        value.make_owned(&self.source);
+                        self.source.next_character();
+                        self.skip_newline();
+                    },
+                    Some(_) => {
+                        let escaped = self.extract_escaped();
+                        value.push_owned(escaped, &self.source);
+                    },
+                },
+                Some(c) => {
+                    self.push_character(&mut value, c);
+                    self.source.next_character();
+                },
+            }
+        }
+    }
+
+    fn extract_numeric(&mut self) -> Token<'a> {
+        let number = self.extract_number();
+
+        if self.would_start_identifier() {
+            return Token::Dimension(number, self.extract_name());
+        }
+
+        if self.source.current_character() == Some('%') {
+            self.source.next_character();
+            return Token::Percentage(number);
+        }
+
+        Token::Number(number)
+    }
+
+    // https://www.w3.org/TR/css-syntax-3/#consume-a-number
+    fn extract_number(&mut self) -> Numeric<'a> {
+        let start = self.source.position().offset;
+        let mut kind = NumericType::Integer;
+
+        if let Some('+') | Some('-') = self.source.current_character() {
+            self.source.next_character();
+        }
+
+        self.skip_digits();
+
+        let current = self.source.current_character();
+        let next = self.source.peek_character();
+        if current == Some('.') && next.is_some_and(|c| c.is_ascii_digit()) {
+            kind = NumericType::Number;
+            self.source.next_character();
+            self.skip_digits();
+        }
+
+        if let Some('e') | Some('E') = self.source.current_character() {
+            let sign = self.source.peek_character().filter(|&c| c == '+' || c == '-');
+            let digit = self.source.peek_nth_character(if sign.is_some() { 2 } else { 1 });
+
+            if digit.is_some_and(|c| c.is_ascii_digit()) {
+                kind = NumericType::Number;
                 self.source.next_character();
-                Token::String(value)
-            },
+                if sign.is_some() {
+                    self.source.next_character();
+                }
+                self.skip_digits();
+            }
+        }
+
+        let representation = self.source.slice(start, self.source.position().offset);
+
+        // the representation is always a valid float, out of range values become infinite so clamp them
+        let value = representation.parse::<f64>()
+            .unwrap_or(0.0)
+            .clamp(f64::MIN, f64::MAX);
+
+        Numeric { value, kind, representation }
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(c) = self.source.current_character() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.source.next_character();
         }
     }
 
-    fn extract_number(&mut self) -> Token {
-        let lhs = self.extract_digits();
+    // consumes up to `max` hex digits and returns them, or `None` if there weren't any
+    fn extract_hex_digits(&mut self, max: usize) -> Option<&'a str> {
+        let start = self.source.position().offset;
+        let mut count = 0;
+        while let Some(c) = self.source.current_character() {
+            if count == max || !c.is_ascii_hexdigit() {
+                break;
+            }
+            count += 1;
+            self.source.next_character();
+        }
+
+        match count {
+            0 => None,
+            _ => Some(self.source.slice(start, self.source.position().offset)),
+        }
+    }
+
+    fn extract_ident_like(&mut self) -> Token<'a> {
+        let name = self.extract_name();
+
+        if self.source.current_character() != Some('(') {
+            return Token::Identifier(name);
+        }
+
+        self.source.next_character();
+        if !name.eq_ignore_ascii_case("url") {
+            return Token::Function(name);
+        }
+
+        // a quoted url is a regular function, the string is tokenized separately
+        loop {
+            let current = self.source.current_character();
+            let next = self.source.peek_character();
+            match (current, next) {
+                (Some(c), Some(n)) if is_whitespace(c) && is_whitespace(n) => {
+                    self.source.next_character();
+                },
+                (Some('"'), _) | (Some('\''), _) => return Token::Function(name),
+                (Some(c), Some('"')) | (Some(c), Some('\'')) if is_whitespace(c) => return Token::Function(name),
+                _ => return self.extract_url(),
+            }
+        }
+    }
+
+    fn extract_url(&mut self) -> Token<'a> {
+        self.skip_whitespace();
+
+        let mut value = ValueBuilder::new(self.source.position().offset);
+        loop {
+            match self.source.current_character() {
+                None => {
+                    self.flag_unexpected("EOF".to_string(), Some(")"));
+                    return Token::Url(value.build(&self.source));
+                },
+                Some(')') => {
+                    self.source.next_character();
+                    return Token::Url(value.build(&self.source));
+                },
+                Some(c) if is_whitespace(c) => {
+                    self.skip_whitespace();
+                    match self.source.current_character() {
+                        None => {
+                            self.flag_unexpected("EOF".to_string(), Some(")"));
+                            return Token::Url(value.build(&self.source));
+                        },
+                        Some(')') => {
+                            self.source.next_character();
+                            return Token::Url(value.build(&self.source));
+                        },
+                        Some(_) => return self.extract_bad_url(),
+                    }
+                },
+                Some('\\') if self.is_valid_escape_next() => {
+                    let escaped = self.extract_escaped();
+                    value.push_owned(escaped, &self.source);
+                },
+                Some(c) if c == '"' || c == '\'' || c == '(' || c == '\\' || is_non_printable(c) => {
+                    self.flag_unexpected(c.to_string(), Some(")"));
+                    return self.extract_bad_url();
+                },
+                Some(c) => {
+                    self.push_character(&mut value, c);
+                    self.source.next_character();
+                },
+            }
+        }
+    }
+
+    // consumes the rest of a broken url so the tokenizer can resume after it
+    fn extract_bad_url(&mut self) -> Token<'a> {
+        while let Some(c) = self.source.current_character() {
+            if c == '\\' && self.is_valid_escape_next() {
+                // escaped `)` doesn't end the url
+                self.extract_escaped();
+                continue;
+            }
 
-        match self.extract_fraction_digits() {
-            Some(rhs) => match format!("{}{}", lhs, rhs).parse::<f32>() {
-                Ok(value) => Token::Float(value),
-                Err(e) => Token::Error(ParserError::InvalidNumber(e.to_string())),
+            self.source.next_character();
+            if c == ')' {
+                break;
+            }
+        }
+
+        Token::BadUrl
+    }
+
+    fn extract_name(&mut self) -> Cow<'a, str> {
+        let mut value = ValueBuilder::new(self.source.position().offset);
+        while let Some(c) = self.source.current_character() {
+            if c == '\\' && self.is_valid_escape_next() {
+                let escaped = self.extract_escaped();
+                value.push_owned(escaped, &self.source);
+                continue;
+            }
+
+            if !is_name_part(c) {
+                break;
+            }
+            self.push_character(&mut value, c);
+            self.source.next_character();
+        }
+
+        value.build(&self.source)
+    }
+
+    // the spec's input preprocessing replaces NULL with U+FFFD
+    fn push_character(&self, value: &mut ValueBuilder, c: char) {
+        match c {
+            '\0' => value.push_owned(char::REPLACEMENT_CHARACTER, &self.source),
+            c => value.push_borrowed(c),
+        }
+    }
+
+    // https://www.w3.org/TR/css-syntax-3/#consume-escaped-code-point
+    // expects the source to be on the `\`, leaves it on the character after the escape
+    fn extract_escaped(&mut self) -> char {
+        let current = match self.source.next_character() {
+            None => {
+                self.flag_unexpected("EOF".to_string(), Some("escaped character"));
+                return char::REPLACEMENT_CHARACTER;
             },
-            _ => match lhs.parse::<i32>() {
-                Ok(value) => Token::Integer(value),
-                Err(e) => Token::Error(ParserError::InvalidNumber(e.to_string())),
+            Some(c) => c,
+        };
+
+        let hex = match self.extract_hex_digits(6) {
+            Some(hex) => hex,
+            None => {
+                self.source.next_character();
+                return replace_null(current);
             },
+        };
+
+        // a single whitespace terminates the escape, eg `\31 0` is `10`
+        match self.source.current_character() {
+            Some(c) if is_newline(c) => self.skip_newline(),
+            Some(c) if is_whitespace(c) => {
+                self.source.next_character();
+            },
+            _ => (),
+        }
+
+        match parse_hex(hex) {
+            0 => char::REPLACEMENT_CHARACTER,
+            code_point => std::char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER),
         }
     }
 
-    fn extract_digits(&mut self) -> String {
-        self.extract_while(|c| match c {
-            '0' ... '9' => true,
-            _ => false,
-        })
+    // treats `\r\n` as a single newline
+    fn skip_newline(&mut self) {
+        if self.source.current_character() == Some('\r') && self.source.peek_character() == Some('\n') {
+            self.source.next_character();
+        }
+        self.source.next_character();
     }
 
-    fn extract_fraction_digits(&mut self) -> Option<String> {
-        let current = self.source.current_character()?;
-        let next = self.source.peek_character()?;
+    fn is_valid_escape_next(&mut self) -> bool {
+        is_valid_escape(self.source.current_character(), self.source.peek_character())
+    }
 
-        if current != '.' || !next.is_digit(10) {
-            return None;
-        }
+    fn extract_whitespace(&mut self) -> Token<'a> {
+        self.skip_whitespace();
+        Token::Whitespace(self.token_text())
+    }
 
-        Some(self.extract_digits())
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.source.current_character() {
+            if !is_whitespace(c) {
+                break;
+            }
+            self.source.next_character();
+        }
     }
 
-    fn extract_word(&mut self) -> Token {
-        Token::Identifier(
-            self.extract_while(is_word_part)
-        )
+    // https://www.w3.org/TR/css-syntax-3/#would-start-an-identifier
+    fn would_start_identifier(&mut self) -> bool {
+        let second = self.source.peek_character();
+        match self.source.current_character() {
+            Some('-') => match second {
+                Some(next) if is_name_start(next) || next == '-' => true,
+                _ => is_valid_escape(second, self.source.peek_nth_character(2)),
+            },
+            Some('\\') => is_valid_escape(Some('\\'), second),
+            Some(c) => is_name_start(c),
+            None => false,
+        }
     }
 
-    fn extract_whitespace(&mut self) -> Token {
-        Token::Whitespace(
-            self.extract_while(|c| c.is_whitespace())
-        )
+    // https://www.w3.org/TR/css-syntax-3/#starts-with-a-number
+    fn would_start_number(&mut self) -> bool {
+        let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+        let second = self.source.peek_character();
+        match self.source.current_character() {
+            Some('+') | Some('-') => match second {
+                Some('.') => is_digit(self.source.peek_nth_character(2)),
+                _ => is_digit(second),
+            },
+            Some('.') => is_digit(second),
+            current => is_digit(current),
+        }
     }
 
     fn skip_comments(&mut self) -> Option<char> {
@@ -242,66 +607,330 @@ impl CssLexer {
             return self.source.current_character();
         }
 
+        self.token_start = self.source.position();
+
         // consume '*'
         self.source.next_character()?;
 
-        while let Some(_) = self.source.next_character() {
+        while self.source.next_character().is_some() {
             if self.is_comment_end_next() {
                 self.source.next_character(); // consume '*'
                 return self.source.next_character(); // consume '/' and return next value
             }
         }
 
+        self.flag_unexpected("EOF".to_string(), Some("*/"));
         None
     }
 
     fn is_comment_start_next(&mut self) -> bool {
-        if let Some(current) = self.source.current_character() {
-            if let Some(next) = self.source.peek_character() {
-                return format!("{}{}", current, next) == "/*".to_string();
-            }
-        }
-
-        false
+        self.source.current_character() == Some('/') && self.source.peek_character() == Some('*')
     }
 
     fn is_comment_end_next(&mut self) -> bool {
-        let current = match self.source.current_character() {
-            None => return false,
-            Some(v) => v,
-        };
+        self.source.current_character() == Some('*') && self.source.peek_character() == Some('/')
+    }
 
-        let next = match self.source.peek_character() {
-            Some(v) => v,
-            None => return false,
-        };
+    // source text of the token currently being extracted
+    fn token_text(&self) -> &'a str {
+        self.source.slice(self.token_start.offset, self.source.position().offset)
+    }
 
-        format!("{}{}", current, next) == "*/".to_string()
+    // span of the token currently being extracted
+    fn span(&self) -> Span {
+        Span::new(self.token_start, self.source.position())
     }
 
-    fn extract_while<T>(&mut self, predicate: T) -> String
-        where T: Fn(char) -> bool
-    {
-        let mut value = self.source.current_character().unwrap().to_string();
-        while let Some(current) = self.source.next_character() {
-            match predicate(current) {
-                true => value.push(current),
-                false => break,
-            }
+    fn flag_unexpected(&mut self, found: String, expected: Option<&str>) {
+        let error = ParserError::UnexpectedToken {
+            found,
+            expected: expected.map(|e| e.to_string()),
+            context: None,
+            span: self.span(),
+        };
+        self.errors.push(error);
+    }
+}
+
+// Yields every token including the final EOF, then stops
+impl<'a> Iterator for CssLexer<'a> {
+    type Item = SpannedToken<'a>;
+
+    fn next(&mut self) -> Option<SpannedToken<'a>> {
+        if self.finished {
+            return None;
         }
 
-        value
+        self.current_spanned_token();
+        let token = self.current.take()?;
+        self.finished = matches!(token.token, Token::EOF);
+
+        Some(token)
     }
 }
 
-fn is_word_part(c: char) -> bool {
-    c.is_alphanumeric() || c == '-' || c == '_'
+fn parse_hex(value: &str) -> u32 {
+    u32::from_str_radix(value, 16).unwrap_or(0)
 }
 
-fn is_start_of_word(c: char) -> bool {
-    c.is_alphabetic() || c == '-' || c == '_'
+// https://www.w3.org/TR/css-syntax-3/#starts-with-a-valid-escape
+fn is_valid_escape(first: Option<char>, second: Option<char>) -> bool {
+    match (first, second) {
+        (Some('\\'), Some(c)) => !is_newline(c),
+        // a `\` at the end of the input is still an escape, it's replaced with U+FFFD
+        (Some('\\'), None) => true,
+        _ => false,
+    }
 }
 
-fn is_start_of_selector(c: char) -> bool {
-    c == '.' || c == '#'
+// the spec's input preprocessing replaces NULL with U+FFFD
+fn replace_null(c: char) -> char {
+    match c {
+        '\0' => char::REPLACEMENT_CHARACTER,
+        c => c,
+    }
+}
+
+fn is_newline(c: char) -> bool {
+    c == '\n' || c == '\r' || c == '\x0C'
+}
+
+fn is_whitespace(c: char) -> bool {
+    is_newline(c) || c == '\t' || c == ' '
+}
+
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\x00'..='\x08' | '\x0B' | '\x0E'..='\x1F' | '\x7F')
+}
+
+// https://www.w3.org/TR/css-syntax-3/#ident-start-code-point
+fn is_name_start(c: char) -> bool {
+    // NULL counts as the U+FFFD it's replaced with
+    c.is_ascii_alphabetic() || c == '_' || c == '\0' || !c.is_ascii()
+}
+
+// https://www.w3.org/TR/css-syntax-3/#ident-code-point
+fn is_name_part(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{Token, Numeric, NumericType, HashType};
+
+    fn tokens(input: &str) -> Vec<Token<'_>> {
+        crate::tokenize(input).map(|token| token.token).collect()
+    }
+
+    fn integer(value: f64, representation: &str) -> Numeric<'_> {
+        Numeric { value, kind: NumericType::Integer, representation }
+    }
+
+    fn number(value: f64, representation: &str) -> Numeric<'_> {
+        Numeric { value, kind: NumericType::Number, representation }
+    }
+
+    #[test]
+    fn unicode_ranges_are_ordinary_tokens() {
+        assert_eq!(tokens("u+a"), [
+            Token::Identifier(Cow::Borrowed("u")),
+            Token::Delim('+'),
+            Token::Identifier(Cow::Borrowed("a")),
+            Token::EOF,
+        ]);
+        assert_eq!(tokens("U+0-7F"), [
+            Token::Identifier(Cow::Borrowed("U")),
+            Token::Number(integer(0.0, "+0")),
+            Token::Dimension(integer(-7.0, "-7"), Cow::Borrowed("F")),
+            Token::EOF,
+        ]);
+    }
+
+    #[test]
+    fn u_plus_selectors_parse() {
+        let output = crate::parse_str("u+a { color: red }");
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(output.stylesheet.rules.len(), 1);
+    }
+
+    #[test]
+    fn tokenizes_every_token_type() {
+        let space = || Token::Whitespace(" ");
+        assert_eq!(tokens("@media #id #1 \"s\" 'x' url(a.png) 50% 12px 3 <!-- --> ! fn( ~"), [
+            Token::AtKeyword("media".into()), space(),
+            Token::Hash("id".into(), HashType::Id), space(),
+            Token::Hash("1".into(), HashType::Unrestricted), space(),
+            Token::String("s".into()), space(),
+            Token::String("x".into()), space(),
+            Token::Url("a.png".into()), space(),
+            Token::Percentage(integer(50.0, "50")), space(),
+            Token::Dimension(integer(12.0, "12"), "px".into()), space(),
+            Token::Number(integer(3.0, "3")), space(),
+            Token::CDO("<!--"), space(),
+            Token::CDC("-->"), space(),
+            Token::Delim('!'), space(),
+            Token::Function("fn".into()), space(),
+            Token::Delim('~'),
+            Token::EOF,
+        ]);
+        assert_eq!(tokens(",:;()[]{}"), [
+            Token::Comma(","), Token::Colon(":"), Token::Semicolon(";"),
+            Token::LeftParen("("), Token::RightParen(")"),
+            Token::LeftBracket("["), Token::RightBracket("]"),
+            Token::LeftBrace("{"), Token::RightBrace("}"),
+            Token::EOF,
+        ]);
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(tokens("a/* b */c /**/"), [
+            Token::Identifier("a".into()),
+            Token::Identifier("c".into()),
+            Token::Whitespace(" "),
+            Token::EOF,
+        ]);
+    }
+
+    #[test]
+    fn recovers_from_bad_strings_and_urls() {
+        assert_eq!(tokens("url(a b) c"), [
+            Token::BadUrl, Token::Whitespace(" "), Token::Identifier("c".into()), Token::EOF,
+        ]);
+        assert_eq!(tokens("url(a\"b) c"), [
+            Token::BadUrl, Token::Whitespace(" "), Token::Identifier("c".into()), Token::EOF,
+        ]);
+        // the newline isn't part of the bad string
+        assert_eq!(tokens("\"a\nb"), [
+            Token::BadString, Token::Whitespace("\n"), Token::Identifier("b".into()), Token::EOF,
+        ]);
+        assert_eq!(tokens("url( \"a\" )")[0], Token::Function("url".into()));
+
+        let mut lexer = crate::tokenize("\"a\nb");
+        lexer.by_ref().for_each(drop);
+        assert_eq!(lexer.take_errors().len(), 1);
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(tokens("\\31 0")[0], Token::Identifier("10".into()));
+        assert_eq!(tokens(".md\\:flex")[1], Token::Identifier("md:flex".into()));
+        assert_eq!(tokens("#\\31 a")[0], Token::Hash("1a".into(), HashType::Id));
+        assert_eq!(tokens("url(a\\)b)")[0], Token::Url("a)b".into()));
+        assert_eq!(tokens("'\\'' \"\\\"\"")[..3], [
+            Token::String("'".into()), Token::Whitespace(" "), Token::String("\"".into()),
+        ]);
+        // an escaped newline continues a string
+        assert_eq!(tokens("\"a\\\r\nb\"")[0], Token::String("ab".into()));
+    }
+
+    #[test]
+    fn replaces_invalid_code_points() {
+        let replacement = char::REPLACEMENT_CHARACTER;
+        // NULL, surrogates and code points past the maximum
+        assert_eq!(tokens("\\0 a")[0], Token::Identifier(format!("{}a", replacement).into()));
+        assert_eq!(tokens("a\\D800")[0], Token::Identifier(format!("a{}", replacement).into()));
+        assert_eq!(tokens("a\\110000")[0], Token::Identifier(format!("a{}", replacement).into()));
+        assert_eq!(tokens("a\0b")[0], Token::Identifier(format!("a{}b", replacement).into()));
+
+        // a `\` at EOF is an error but still an escape
+        let mut lexer = crate::tokenize("a\\");
+        assert_eq!(lexer.next().map(|token| token.token), Some(Token::Identifier(format!("a{}", replacement).into())));
+        assert_eq!(lexer.take_errors().len(), 1);
+    }
+
+    #[test]
+    fn non_ascii_code_points_start_identifiers() {
+        assert_eq!(tokens("héllo 🎉x"), [
+            Token::Identifier("héllo".into()),
+            Token::Whitespace(" "),
+            Token::Identifier("🎉x".into()),
+            Token::EOF,
+        ]);
+    }
+
+    #[test]
+    fn tokenizes_signed_and_exponent_numbers() {
+        assert_eq!(tokens("+.5e-3")[0], Token::Number(number(0.0005, "+.5e-3")));
+        assert_eq!(tokens("-.5")[0], Token::Number(number(-0.5, "-.5")));
+        assert_eq!(tokens("1E+2")[0], Token::Number(number(100.0, "1E+2")));
+        assert_eq!(tokens("-12")[0], Token::Number(integer(-12.0, "-12")));
+        assert_eq!(tokens("+1.50%")[0], Token::Percentage(number(1.5, "+1.50")));
+        assert_eq!(tokens("-2.5e1px")[0], Token::Dimension(number(-25.0, "-2.5e1"), "px".into()));
+    }
+
+    #[test]
+    fn stops_numbers_where_the_spec_does() {
+        // no digit after the `.` or the `e`
+        assert_eq!(tokens("1."), [Token::Number(integer(1.0, "1")), Token::Delim('.'), Token::EOF]);
+        assert_eq!(tokens("1e")[0], Token::Dimension(integer(1.0, "1"), "e".into()));
+        assert_eq!(tokens("1e+"), [Token::Dimension(integer(1.0, "1"), "e".into()), Token::Delim('+'), Token::EOF]);
+        assert_eq!(tokens(".5.5"), [Token::Number(number(0.5, ".5")), Token::Number(number(0.5, ".5")), Token::EOF]);
+        assert_eq!(tokens("+-1"), [Token::Delim('+'), Token::Number(integer(-1.0, "-1")), Token::EOF]);
+        assert_eq!(tokens("-x")[0], Token::Identifier("-x".into()));
+    }
+
+    #[test]
+    fn clamps_out_of_range_numbers() {
+        assert_eq!(tokens("1e999")[0], Token::Number(number(f64::MAX, "1e999")));
+        assert_eq!(tokens("-1e999")[0], Token::Number(number(f64::MIN, "-1e999")));
+        assert_eq!(tokens("99999999999")[0], Token::Number(integer(99999999999.0, "99999999999")));
+
+        let mut lexer = crate::tokenize("1e999");
+        lexer.by_ref().for_each(drop);
+        assert!(lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn iterates_up_to_and_including_eof() {
+        assert_eq!(crate::tokenize("a").count(), 2);
+        assert_eq!(crate::tokenize("").map(|token| token.token).collect::<Vec<_>>(), [Token::EOF]);
+
+        let mut lexer = crate::tokenize("a b");
+        assert_eq!(lexer.by_ref().count(), 4);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn yields_spans_with_tokens() {
+        let spans = crate::tokenize("a\n  bc")
+            .map(|token| (token.span.start.line, token.span.start.column, token.span.start.offset, token.span.end.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(spans, [(1, 1, 0, 1), (1, 2, 1, 4), (2, 3, 4, 6), (2, 5, 6, 6)]);
+    }
+
+    #[test]
+    fn collects_errors_without_stopping() {
+        let mut lexer = crate::tokenize("'a\n/* b");
+        let tokens = lexer.by_ref().map(|token| token.token).collect::<Vec<_>>();
+        assert_eq!(tokens, [Token::BadString, Token::Whitespace("\n"), Token::EOF]);
+        assert_eq!(lexer.take_errors().len(), 2);
+        assert!(lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn borrows_values_unless_escaped() {
+        let borrowed = |token: &Token| match token {
+            Token::Identifier(value) | Token::Function(value) | Token::AtKeyword(value) | Token::Hash(value, _)
+            | Token::String(value) | Token::Url(value) | Token::Dimension(_, value) => {
+                matches!(value, Cow::Borrowed(..))
+            },
+            _ => true,
+        };
+
+        let input = "@media #main .a-b \"str\" url(x.png) 12px fn(";
+        assert!(tokens(input).iter().all(borrowed));
+        for escaped in ["a\\62 c", "#\\31 a", "'a\\'b'", "url(a\\)b)", "1\\70x", "a\0b"] {
+            assert!(!borrowed(&tokens(escaped)[0]), "{}", escaped);
+        }
+
+        // the borrowed values point into the input
+        let input = String::from("abc");
+        match &tokens(&input)[0] {
+            Token::Identifier(Cow::Borrowed(value)) => assert_eq!(value.as_ptr(), input.as_ptr()),
+            token => panic!("{:?} isn't borrowed", token),
+        }
+    }
 }
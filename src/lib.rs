@@ -1,24 +1,153 @@
-use std::io::BufReader;
+use std::borrow::Cow;
+use std::io::Read;
 use std::fs::File;
 
 use crate::source::{Source};
 use crate::lexer::{CssLexer};
 use crate::parser::{CssParser};
-use crate::style_sheet::{StyleSheet};
+use crate::error::ParserError;
+use crate::style_sheet::ComponentValue;
 
-mod lexer;
-mod error;
+pub use crate::parser::{ParseOptions, ParseOutput};
+
+pub mod lexer;
+pub mod error;
 mod selector_parser;
 mod property_parser;
+mod at_rule_parser;
 mod parser;
-mod style_sheet;
+mod value_parser;
+mod color_parser;
+mod color;
+mod calc_parser;
+mod calc;
+pub mod style_sheet;
+pub mod selectors;
+pub mod dom;
+pub mod cascade;
+pub mod custom_properties;
+pub mod properties;
+mod serialize;
 mod source;
+pub mod span;
+
+pub fn parse_file(path: &str) -> std::io::Result<ParseOutput> {
+    parse_reader(File::open(path)?)
+}
+
+pub fn parse_reader<R: Read>(reader: R) -> std::io::Result<ParseOutput> {
+    Ok(parse_bytes(&read_bytes(reader)?))
+}
+
+// Invalid UTF-8 is replaced with U+FFFD and a leading byte order mark is dropped, as the spec's
+// decoding does. Readers and files are decoded the same way.
+pub fn parse_bytes(input: &[u8]) -> ParseOutput {
+    parse_str(&decode(input))
+}
+
+pub fn parse_str(input: &str) -> ParseOutput {
+    parse_source(Source::new(input), ParseOptions::default())
+}
 
-pub fn parse_file(path: &str) -> std::io::Result<StyleSheet> {
-    let file = File::open(path)?;
-    let source = Source::new(BufReader::new(file));
-    let lexer = CssLexer::new(source);
+// In strict mode the first error is returned instead of the recovered output.
+pub fn parse_str_with_options(input: &str, options: ParseOptions) -> Result<ParseOutput, ParserError> {
+    let output = parse_source(Source::new(input), options);
+    match output.errors.first() {
+        Some(error) if options.strict => Err(error.clone()),
+        _ => Ok(output),
+    }
+}
+
+pub fn parse_bytes_with_options(input: &[u8], options: ParseOptions) -> Result<ParseOutput, ParserError> {
+    parse_str_with_options(&decode(input), options)
+}
+
+// A parse error in strict mode is returned as an `InvalidData` error wrapping the `ParserError`.
+pub fn parse_reader_with_options<R: Read>(reader: R, options: ParseOptions) -> std::io::Result<ParseOutput> {
+    parse_bytes_with_options(&read_bytes(reader)?, options)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+}
+
+pub fn parse_file_with_options(path: &str, options: ParseOptions) -> std::io::Result<ParseOutput> {
+    parse_reader_with_options(File::open(path)?, options)
+}
+
+// The returned lexer iterates over every token up to and including EOF,
+// parse errors found along the way are available from `CssLexer::take_errors`.
+pub fn tokenize(input: &str) -> CssLexer<'_> {
+    CssLexer::new(Source::new(input))
+}
 
-    Ok(CssParser::new(lexer).parse())
+// Parses a list of component values, eg a declaration value on its own. Whitespace is kept
+// and errors are ignored.
+pub fn parse_component_values(input: &str) -> Vec<ComponentValue> {
+    let mut parser = CssParser::new(CssLexer::new(Source::new(input)), ParseOptions::default());
+    std::iter::from_fn(|| parser.parse_component_value()).collect()
 }
 
+fn parse_source(source: Source, options: ParseOptions) -> ParseOutput {
+    CssParser::new(CssLexer::new(source), options).parse()
+}
+
+fn read_bytes<R: Read>(mut reader: R) -> std::io::Result<Vec<u8>> {
+    let mut input = vec![];
+    reader.read_to_end(&mut input)?;
+
+    Ok(input)
+}
+
+fn decode(input: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ParseOptions;
+    use crate::error::ParserError;
+
+    const STRICT: ParseOptions = ParseOptions { strict: true };
+
+    #[test]
+    fn decodes_readers_and_bytes_the_same_way() {
+        let input = b"\xEF\xBB\xBFa { content: '\xFF' }";
+        let from_bytes = crate::parse_bytes(input);
+        let from_reader = crate::parse_reader(&input[..]).unwrap();
+
+        assert_eq!(from_bytes.stylesheet.rules.len(), 1);
+        assert!(from_bytes.errors.is_empty(), "{:?}", from_bytes.errors);
+        assert_eq!(format!("{:?}", from_bytes.stylesheet), format!("{:?}", from_reader.stylesheet));
+        assert!(format!("{:?}", from_reader.stylesheet).contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn strict_mode_works_for_every_input() {
+        let broken = "a { color red } b { color: blue }";
+        assert!(crate::parse_str_with_options(broken, STRICT).is_err());
+        assert!(crate::parse_bytes_with_options(broken.as_bytes(), STRICT).is_err());
+
+        let error = crate::parse_reader_with_options(broken.as_bytes(), STRICT).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.get_ref().is_some_and(|error| error.is::<ParserError>()));
+
+        let lenient = crate::parse_reader_with_options(broken.as_bytes(), ParseOptions::default()).unwrap();
+        assert_eq!(lenient.errors.len(), 1);
+        assert_eq!(lenient.stylesheet.rules.len(), 2);
+    }
+
+    #[test]
+    fn strict_mode_returns_the_first_collected_error() {
+        let css = "a { color red } $ {} b {} c { width: 1px height: 2px }";
+        let lenient = crate::parse_str(css);
+        assert_eq!(lenient.errors.len(), 2);
+        // the rules left empty and the missing `;` are only warnings
+        assert_eq!(lenient.warnings.len(), 3);
+        assert_eq!(lenient.stylesheet.rules.len(), 3);
+
+        let error = crate::parse_str_with_options(css, STRICT).unwrap_err();
+        assert_eq!(error, lenient.errors[0]);
+
+        let valid = crate::parse_str_with_options("a { color: red } b {}", STRICT).unwrap();
+        assert!(valid.errors.is_empty());
+        assert_eq!(valid.warnings.len(), 1);
+    }
+}
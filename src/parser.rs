@@ -1,76 +1,272 @@
-use crate::lexer::{Token, CssLexer};
-use crate::error::{ErrorHandler, ParserError};
-use crate::selector_parser::{SelectorParser, is_token_selector};
+use std::collections::HashMap;
+
+use crate::lexer::{Token, CssLexer, Numeric};
+use crate::error::{ErrorHandler, ParserError, ParserWarning};
+use crate::selector_parser::SelectorParser;
 use crate::property_parser::PropertyParser;
-use crate::style_sheet::{StyleSheet, Rule};
+use crate::at_rule_parser::AtRuleParser;
+use crate::style_sheet::{StyleSheet, CssRule, Rule, ComponentValue, BlockKind, Number};
+use crate::span::Span;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParseOptions {
+    // stop at the first error instead of recovering
+    pub strict: bool,
+}
+
+#[derive(Debug)]
+pub struct ParseOutput {
+    pub stylesheet: StyleSheet,
+    pub errors: Vec<ParserError>,
+    pub warnings: Vec<ParserWarning>,
+}
 
 #[derive(Debug)]
-pub struct CssParser {
-    lexer: CssLexer,
+pub struct CssParser<'i> {
+    lexer: CssLexer<'i>,
     pub(crate) error_handler: ErrorHandler,
+    // prefixes declared by `@namespace` so far, the default namespace is under ""
+    pub(crate) namespaces: HashMap<String, String>,
+    // `@namespace` is only allowed before any rule but `@charset`, `@import` and other `@namespace` rules
+    pub(crate) namespaces_allowed: bool,
+    // span of the last consumed token that wasn't whitespace
+    previous_span: Span,
 }
 
-impl CssParser {
-    pub fn new(lexer: CssLexer) -> CssParser {
+impl<'i> CssParser<'i> {
+    pub fn new(lexer: CssLexer<'i>, options: ParseOptions) -> CssParser<'i> {
         CssParser {
             lexer,
-            error_handler: ErrorHandler::new(),
+            error_handler: ErrorHandler::new(options.strict),
+            namespaces: HashMap::new(),
+            namespaces_allowed: true,
+            previous_span: Span::default(),
         }
     }
 
-    pub fn parse(&mut self) -> StyleSheet {
-        let mut rules: Vec<Rule> = vec![];
+    pub fn parse(&mut self) -> ParseOutput {
+        let stylesheet = self.parse_style_sheet();
+
+        ParseOutput {
+            stylesheet,
+            errors: std::mem::take(&mut self.error_handler.errors),
+            warnings: std::mem::take(&mut self.error_handler.warnings),
+        }
+    }
+
+    fn parse_style_sheet(&mut self) -> StyleSheet {
+        StyleSheet { rules: self.parse_rule_list(true) }
+    }
+
+    // Nested rule lists, eg inside `@media`, end at the `}` of their block which is left for the caller
+    pub(crate) fn parse_rule_list(&mut self, top_level: bool) -> Vec<CssRule> {
+        let mut rules: Vec<CssRule> = vec![];
 
         loop {
-            let token = self.current_token(true);
-            if is_token_selector(&token) {
-                rules.push(self.parse_rule());
-                if Token::EOF == self.current_token(true) {
-                    break;
-                }
-            } else {
-                self.error_handler.flag(&ParserError::UnexpectedToken {
-                    found: token.to_string(),
-                    expected: None,
-                    context: None,
-                });
+            if self.error_handler.is_aborted() {
+                break;
+            }
+
+            match self.current_token(true) {
+                Token::EOF => break,
+                Token::RightBrace(..) if !top_level => break,
+                // html comment tokens are only allowed around the top level rules
+                Token::CDO(..) | Token::CDC(..) if top_level => {
+                    self.try_next_token(true);
+                },
+                Token::AtKeyword(name) => {
+                    if !["charset", "import", "namespace"].iter().any(|allowed| name.eq_ignore_ascii_case(allowed)) {
+                        self.namespaces_allowed = false;
+                    }
+                    let rule = AtRuleParser::new(self).parse();
+                    rules.push(CssRule::At(rule));
+                },
+                // anything else starts a qualified rule, which is dropped if it turns out to be invalid
+                _ => {
+                    self.namespaces_allowed = false;
+                    if let Some(rule) = self.parse_rule(top_level) {
+                        rules.push(CssRule::Style(rule));
+                    }
+                },
             }
         };
 
-        StyleSheet { rules }
+        rules
     }
 
-    fn parse_rule(&mut self) -> Rule {
-        let selectors = SelectorParser::new(self).parse();
-        Rule {
+    fn parse_rule(&mut self, top_level: bool) -> Option<Rule> {
+        let start = self.current_span();
+        let selectors = match SelectorParser::new(self).parse() {
+            Some(selectors) => selectors,
+            None => {
+                self.skip_qualified_rule(top_level);
+                return None;
+            },
+        };
+
+        let properties = PropertyParser::new(self).parse();
+        let span = start.to(self.previous_span);
+        if properties.is_empty() {
+            self.error_handler.warn(ParserWarning::EmptyRule(span));
+        }
+
+        Some(Rule {
             selectors,
-            properties: PropertyParser::new(self).parse(),
+            properties,
+            span,
+        })
+    }
+
+    // Skips the rest of an invalid qualified rule up to the end of its block
+    fn skip_qualified_rule(&mut self, top_level: bool) {
+        loop {
+            match self.current_token(false) {
+                Token::EOF => return,
+                // the `}` closes the parent block
+                Token::RightBrace(..) if !top_level => return,
+                Token::LeftBrace(..) => {
+                    self.skip_component_value();
+                    return;
+                },
+                _ => self.skip_component_value(),
+            }
         }
     }
 
-    pub(crate) fn try_next_token(&mut self, skip_whitespace: bool) -> Token {
-        let token = self.lexer.next_token();
-        match token {
-            Token::Error(error) => {
-                self.error_handler.flag(error);
-                token
-            },
-            Token::Whitespace(..) if skip_whitespace => {
-                self.skip_whitespace_tokens();
-                self.lexer.current_token()
-            },
-            _ => token,
-        }.clone()
+    // Skips the rest of an invalid declaration, the `}` that ends the declaration list is left for the caller
+    pub(crate) fn skip_declaration(&mut self) {
+        loop {
+            match self.current_token(false) {
+                Token::EOF | Token::RightBrace(..) => return,
+                Token::Semicolon(..) => {
+                    self.try_next_token(false);
+                    return;
+                },
+                _ => self.skip_component_value(),
+            }
+        }
     }
 
-    pub(crate) fn current_token(&mut self, skip_whitespace: bool) -> Token {
+    // Consumes a single component value, blocks and functions are consumed up to their matching end
+    pub(crate) fn skip_component_value(&mut self) {
+        let mut closing: Vec<char> = vec![];
+
+        loop {
+            match self.current_token(false) {
+                Token::EOF => return,
+                Token::LeftBrace(..) => closing.push('}'),
+                Token::LeftBracket(..) => closing.push(']'),
+                Token::LeftParen(..) | Token::Function(..) => closing.push(')'),
+                Token::RightBrace(..) if closing.last() == Some(&'}') => {
+                    closing.pop();
+                },
+                Token::RightBracket(..) if closing.last() == Some(&']') => {
+                    closing.pop();
+                },
+                Token::RightParen(..) if closing.last() == Some(&')') => {
+                    closing.pop();
+                },
+                _ => (),
+            }
+
+            self.try_next_token(false);
+            if closing.is_empty() {
+                return;
+            }
+        }
+    }
+
+    // Consumes a single component value like `skip_component_value` but keeps it, `None` at EOF.
+    // Blocks and functions left open at EOF are closed there.
+    pub(crate) fn parse_component_value(&mut self) -> Option<ComponentValue> {
+        // the blocks and functions being consumed, innermost last
+        let mut open: Vec<ComponentValue> = vec![];
+
+        loop {
+            let token = self.current_token(false);
+            let closing = match open.last() {
+                Some(ComponentValue::Block(BlockKind::Brace, _)) => matches!(token, Token::RightBrace(..)),
+                Some(ComponentValue::Block(BlockKind::Bracket, _)) => matches!(token, Token::RightBracket(..)),
+                Some(_) => matches!(token, Token::RightParen(..)),
+                None => false,
+            };
+
+            let value = match token {
+                Token::EOF if open.is_empty() => return None,
+                Token::EOF => open.pop(),
+                _ if closing => open.pop(),
+                Token::LeftBrace(..) => {
+                    open.push(ComponentValue::Block(BlockKind::Brace, vec![]));
+                    None
+                },
+                Token::LeftBracket(..) => {
+                    open.push(ComponentValue::Block(BlockKind::Bracket, vec![]));
+                    None
+                },
+                Token::LeftParen(..) => {
+                    open.push(ComponentValue::Block(BlockKind::Parenthesis, vec![]));
+                    None
+                },
+                Token::Function(name) => {
+                    open.push(ComponentValue::Function(name.into_owned(), vec![]));
+                    None
+                },
+                token => Some(preserved_token(token)),
+            };
+
+            if !matches!(self.current_token(false), Token::EOF) {
+                self.try_next_token(false);
+            }
+
+            if let Some(value) = value {
+                match open.last_mut() {
+                    Some(ComponentValue::Block(_, values)) | Some(ComponentValue::Function(_, values)) => {
+                        values.push(value)
+                    },
+                    _ => return Some(value),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn try_next_token(&mut self, skip_whitespace: bool) -> Token<'i> {
+        if !matches!(self.lexer.current_token(), Token::Whitespace(..)) {
+            self.previous_span = self.lexer.current_span();
+        }
+
+        self.lexer.next_token();
+        self.current_token(skip_whitespace)
+    }
+
+    pub(crate) fn current_token(&mut self, skip_whitespace: bool) -> Token<'i> {
         if skip_whitespace {
             self.skip_whitespace_tokens();
         }
-        self.lexer.current_token().clone()
+        let token = self.lexer.current_token().clone();
+        self.flag_lexer_errors();
+
+        token
+    }
+
+    pub(crate) fn current_text(&mut self) -> &'i str {
+        self.lexer.current_text()
+    }
+
+    pub(crate) fn current_span(&mut self) -> Span {
+        self.lexer.current_span()
     }
 
-    fn skip_whitespace_tokens(&mut self) -> () {
+    pub(crate) fn previous_span(&self) -> Span {
+        self.previous_span
+    }
+
+    fn flag_lexer_errors(&mut self) {
+        for error in self.lexer.take_errors() {
+            self.error_handler.flag(&error);
+        }
+    }
+
+    fn skip_whitespace_tokens(&mut self) {
         loop {
             match self.lexer.current_token() {
                 Token::Whitespace(..) => {
@@ -82,3 +278,84 @@ impl CssParser {
         }
     }
 }
+
+// blocks, functions and EOF are handled by `parse_component_value`
+fn preserved_token(token: Token) -> ComponentValue {
+    let number = |numeric: &Numeric| Number { value: numeric.value, kind: numeric.kind };
+    match token {
+        Token::Identifier(value) => ComponentValue::Identifier(value.into_owned()),
+        Token::AtKeyword(value) => ComponentValue::AtKeyword(value.into_owned()),
+        Token::Hash(value, kind) => ComponentValue::Hash(value.into_owned(), kind),
+        Token::String(value) => ComponentValue::String(value.into_owned()),
+        Token::BadString => ComponentValue::BadString,
+        Token::Url(value) => ComponentValue::Url(value.into_owned()),
+        Token::BadUrl => ComponentValue::BadUrl,
+        Token::Delim(c) => ComponentValue::Delim(c),
+        Token::Number(numeric) => ComponentValue::Number(number(&numeric)),
+        Token::Percentage(numeric) => ComponentValue::Percentage(number(&numeric)),
+        Token::Dimension(numeric, unit) => ComponentValue::Dimension(number(&numeric), unit.into_owned()),
+        Token::Whitespace(..) => ComponentValue::Whitespace,
+        Token::CDO(..) => ComponentValue::CDO,
+        Token::CDC(..) => ComponentValue::CDC,
+        Token::Colon(..) => ComponentValue::Colon,
+        Token::Semicolon(..) => ComponentValue::Semicolon,
+        Token::Comma(..) => ComponentValue::Comma,
+        Token::RightBracket(..) => ComponentValue::RightBracket,
+        Token::RightParen(..) => ComponentValue::RightParen,
+        Token::RightBrace(..) => ComponentValue::RightBrace,
+        Token::LeftBracket(..) | Token::LeftParen(..) | Token::LeftBrace(..) | Token::Function(..) | Token::EOF => {
+            unreachable!("{} isn't a preserved token", token)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::style_sheet::CssRule;
+
+    // `rules` has the declaration names of each style rule, separated by spaces
+    fn assert_parses(css: &str, rules: &[&str], errors: usize) {
+        let output = crate::parse_str(css);
+        let actual = output.stylesheet.rules.iter().filter_map(|rule| match rule {
+            CssRule::Style(rule) => {
+                Some(rule.properties.iter().map(|property| property.name.as_str()).collect::<Vec<_>>().join(" "))
+            },
+            _ => None,
+        });
+        assert_eq!(actual.collect::<Vec<_>>(), rules, "{}", css);
+        assert_eq!(output.errors.len(), errors, "{}: {:?}", css, output.errors);
+    }
+
+    #[test]
+    fn drops_invalid_declarations_up_to_the_next_semicolon() {
+        assert_parses("a { color: red; 12: x; width: 1px } b { top: 0 }", &["color width", "top"], 1);
+        assert_parses("a { color: red; width 1px; height: 2px }", &["color height"], 1);
+        assert_parses("a { ] ; b: c }", &["b"], 1);
+        // `;` inside a block or function doesn't end the declaration
+        assert_parses("a { x: (;) [;] f(;); y: z }", &["x y"], 0);
+        assert_parses("a { b: c; ; ; d: e }", &["b d"], 0);
+    }
+
+    #[test]
+    fn drops_invalid_qualified_rules_with_their_block() {
+        assert_parses("$$ a { x: y } b { color: red }", &["color"], 1);
+        assert_parses("a ] b { x: { y } } c { x: y }", &["x"], 1);
+        // a `}` at the top level is part of the broken rule's prelude
+        assert_parses(") } d { x: y } e { x: y }", &["x"], 1);
+    }
+
+    #[test]
+    fn closes_blocks_left_open_at_eof() {
+        assert_parses("a { color: red", &["color"], 1);
+        assert_parses("@media print { a { color: red", &[], 2);
+    }
+
+    #[test]
+    fn always_makes_progress() {
+        for css in ["}", "{", ";", ")", "]", "!", "a {", "a { :", "a { b: }", "@", "@media", "@media {", "url(", "'", "/*", "\\"] {
+            let _ = crate::parse_str(css);
+            let _ = crate::parse_str(&format!("a {{ {} }}", css));
+            let _ = crate::parse_str(&format!("@media print {{ {} }}", css));
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::style_sheet::ComponentValue;
+
+#[derive(Debug)]
+pub struct PropertyInfo {
+    pub name: &'static str,
+    pub inherited: bool,
+    // as written in the specs, resolved by the user agent where they say so
+    pub initial: &'static str,
+}
+
+impl PropertyInfo {
+    pub fn initial_value(&self) -> Vec<ComponentValue> {
+        crate::parse_component_values(self.initial)
+    }
+}
+
+macro_rules! properties {
+    ($($name:literal => $inherited:literal, $initial:literal;)*) => {
+        &[$(PropertyInfo { name: $name, inherited: $inherited, initial: $initial }),*]
+    };
+}
+
+// Longhand properties only, shorthands are expected to be expanded before the cascade. Sorted
+// by name for `lookup`, which the tests check.
+pub static PROPERTIES: &[PropertyInfo] = properties! {
+    "align-content" => false, "normal";
+    "align-items" => false, "normal";
+    "align-self" => false, "auto";
+    "background-attachment" => false, "scroll";
+    "background-clip" => false, "border-box";
+    "background-color" => false, "transparent";
+    "background-image" => false, "none";
+    "background-origin" => false, "padding-box";
+    "background-position" => false, "0% 0%";
+    "background-repeat" => false, "repeat";
+    "background-size" => false, "auto";
+    "border-bottom-color" => false, "currentcolor";
+    "border-bottom-left-radius" => false, "0";
+    "border-bottom-right-radius" => false, "0";
+    "border-bottom-style" => false, "none";
+    "border-bottom-width" => false, "medium";
+    "border-collapse" => true, "separate";
+    "border-left-color" => false, "currentcolor";
+    "border-left-style" => false, "none";
+    "border-left-width" => false, "medium";
+    "border-right-color" => false, "currentcolor";
+    "border-right-style" => false, "none";
+    "border-right-width" => false, "medium";
+    "border-spacing" => true, "0px 0px";
+    "border-top-color" => false, "currentcolor";
+    "border-top-left-radius" => false, "0";
+    "border-top-right-radius" => false, "0";
+    "border-top-style" => false, "none";
+    "border-top-width" => false, "medium";
+    "bottom" => false, "auto";
+    "box-shadow" => false, "none";
+    "box-sizing" => false, "content-box";
+    "caption-side" => true, "top";
+    "clear" => false, "none";
+    "color" => true, "canvastext";
+    "column-gap" => false, "normal";
+    "content" => false, "normal";
+    "cursor" => true, "auto";
+    "direction" => true, "ltr";
+    "display" => false, "inline";
+    "empty-cells" => true, "show";
+    "flex-basis" => false, "auto";
+    "flex-direction" => false, "row";
+    "flex-grow" => false, "0";
+    "flex-shrink" => false, "1";
+    "flex-wrap" => false, "nowrap";
+    "float" => false, "none";
+    "font-family" => true, "serif";
+    "font-size" => true, "medium";
+    "font-stretch" => true, "normal";
+    "font-style" => true, "normal";
+    "font-variant" => true, "normal";
+    "font-weight" => true, "normal";
+    "grid-template-columns" => false, "none";
+    "grid-template-rows" => false, "none";
+    "height" => false, "auto";
+    "justify-content" => false, "normal";
+    "left" => false, "auto";
+    "letter-spacing" => true, "normal";
+    "line-height" => true, "normal";
+    "list-style-image" => true, "none";
+    "list-style-position" => true, "outside";
+    "list-style-type" => true, "disc";
+    "margin-bottom" => false, "0";
+    "margin-left" => false, "0";
+    "margin-right" => false, "0";
+    "margin-top" => false, "0";
+    "max-height" => false, "none";
+    "max-width" => false, "none";
+    "min-height" => false, "auto";
+    "min-width" => false, "auto";
+    "opacity" => false, "1";
+    "order" => false, "0";
+    "outline-color" => false, "auto";
+    "outline-offset" => false, "0";
+    "outline-style" => false, "none";
+    "outline-width" => false, "medium";
+    "overflow-x" => false, "visible";
+    "overflow-y" => false, "visible";
+    "padding-bottom" => false, "0";
+    "padding-left" => false, "0";
+    "padding-right" => false, "0";
+    "padding-top" => false, "0";
+    "pointer-events" => true, "auto";
+    "position" => false, "static";
+    "quotes" => true, "auto";
+    "right" => false, "auto";
+    "row-gap" => false, "normal";
+    "table-layout" => false, "auto";
+    "text-align" => true, "start";
+    "text-decoration-color" => false, "currentcolor";
+    "text-decoration-line" => false, "none";
+    "text-decoration-style" => false, "solid";
+    "text-indent" => true, "0";
+    "text-overflow" => false, "clip";
+    "text-shadow" => true, "none";
+    "text-transform" => true, "none";
+    "top" => false, "auto";
+    "transform" => false, "none";
+    "transition-delay" => false, "0s";
+    "transition-duration" => false, "0s";
+    "transition-property" => false, "all";
+    "transition-timing-function" => false, "ease";
+    "vertical-align" => false, "baseline";
+    "visibility" => true, "visible";
+    "white-space" => true, "normal";
+    "width" => false, "auto";
+    "word-break" => true, "normal";
+    "word-spacing" => true, "normal";
+    "z-index" => false, "auto";
+};
+
+// `name` has to be lowercase
+pub fn lookup(name: &str) -> Option<&'static PropertyInfo> {
+    PROPERTIES.binary_search_by(|property| property.name.cmp(name))
+        .ok()
+        .map(|index| &PROPERTIES[index])
+}
+
+// `color` and the `*-color` longhands, where a named color is a color rather than a keyword
+pub fn takes_color(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    !name.starts_with("--") && (name == "color" || name.ends_with("-color"))
+}
+
+// custom properties always inherit, unknown properties never do
+pub fn is_inherited(name: &str) -> bool {
+    name.starts_with("--") || lookup(name).is_some_and(|property| property.inherited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PROPERTIES, lookup, is_inherited, takes_color};
+
+    #[test]
+    fn properties_are_sorted_and_unique() {
+        for pair in PROPERTIES.windows(2) {
+            assert!(pair[0].name < pair[1].name, "{} should come after {}", pair[0].name, pair[1].name);
+        }
+    }
+
+    #[test]
+    fn looks_up_every_property() {
+        for property in PROPERTIES {
+            assert_eq!(lookup(property.name).map(|found| found.name), Some(property.name));
+        }
+        assert!(lookup("colour").is_none());
+    }
+
+    #[test]
+    fn custom_properties_inherit() {
+        assert!(is_inherited("--anything"));
+        assert!(is_inherited("color"));
+        assert!(!is_inherited("width"));
+        assert!(!is_inherited("unknown"));
+    }
+
+    #[test]
+    fn color_properties() {
+        assert!(takes_color("color"));
+        assert!(takes_color("Background-Color"));
+        assert!(takes_color("border-top-color"));
+        assert!(!takes_color("--brand-color"));
+        assert!(!takes_color("font-family"));
+    }
+}
@@ -1,16 +1,16 @@
 use crate::parser::{CssParser};
 use crate::lexer::Token;
-use crate::error::ParserError;
-use crate::style_sheet::{Property, DataType};
+use crate::error::{ParserError, ParserWarning};
+use crate::style_sheet::{Property, ComponentValue};
+use crate::value_parser;
 
 // TODO make semicolons required except for the last property
-// TODO skip property if there's a syntax error
-pub struct PropertyParser<'a> {
-    css_parser: &'a mut CssParser,
+pub struct PropertyParser<'a, 'i> {
+    css_parser: &'a mut CssParser<'i>,
 }
 
-impl<'a> PropertyParser<'a> {
-    pub fn new(css_parser: &'a mut CssParser) -> PropertyParser {
+impl<'a, 'i> PropertyParser<'a, 'i> {
+    pub fn new(css_parser: &'a mut CssParser<'i>) -> PropertyParser<'a, 'i> {
         PropertyParser { css_parser }
     }
 
@@ -25,10 +25,12 @@ impl<'a> PropertyParser<'a> {
                 return vec![]
             },
             token => {
+                let span = self.css_parser.current_span();
                 self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
                     found: format!("{}", token),
                     expected: Some("{".to_string()),
                     context: None,
+                    span,
                 });
                 token
             },
@@ -36,49 +38,132 @@ impl<'a> PropertyParser<'a> {
 
         let mut properties: Vec<Property> = vec![];
         loop {
-            let name = match self.css_parser.current_token(true) {
-                Token::Identifier(value) => value.to_string(),
-                t => {
+            if self.css_parser.error_handler.is_aborted() {
+                break;
+            }
+
+            match self.css_parser.current_token(true) {
+                Token::RightBrace(..) => {
+                    self.css_parser.try_next_token(true);
+                    break;
+                },
+                Token::EOF => {
+                    let span = self.css_parser.current_span();
                     self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: format!("{}", t),
-                        expected: Some("identifier".to_string()),
+                        found: "EOF".to_string(),
+                        expected: Some("}".to_string()),
                         context: None,
+                        span,
                     });
-                    continue;
+                    break;
                 },
-            };
-
-            match self.css_parser.try_next_token(true) {
-                Token::Colon(..) => self.css_parser.try_next_token(true),
-                token => {
+                // empty declaration
+                Token::Semicolon(..) => {
+                    self.css_parser.try_next_token(true);
+                },
+                Token::Identifier(name) => {
+                    if let Some(property) = self.parse_property(name.into_owned()) {
+                        properties.push(property);
+                    }
+                },
+                t => {
+                    let span = self.css_parser.current_span();
                     self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: format!("{}", token),
-                        expected: Some(":".to_string()),
+                        found: format!("{}", t),
+                        expected: Some("identifier".to_string()),
                         context: None,
+                        span,
                     });
-                    token
+                    self.css_parser.skip_declaration();
                 },
-            };
+            }
+        }
 
-            let value = match self.css_parser.current_token(true) {
-                Token::Identifier(val) | Token::String(val) => {
-                    DataType::Keyword(val)
-                },
-                _ => DataType::Keyword("don't know yet!".to_string()),
-            };
+        properties
+    }
 
-            properties.push(Property { name, value });
+    // Invalid declarations are dropped up to the next `;` or `}`
+    fn parse_property(&mut self, name: String) -> Option<Property> {
+        let start = self.css_parser.current_span();
+        match self.css_parser.try_next_token(true) {
+            Token::Colon(..) => self.css_parser.try_next_token(true),
+            token => {
+                let span = self.css_parser.current_span();
+                self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
+                    found: format!("{}", token),
+                    expected: Some(":".to_string()),
+                    context: None,
+                    span,
+                });
+                self.css_parser.skip_declaration();
+                return None;
+            },
+        };
 
-            if let Token::Semicolon(..) = self.css_parser.try_next_token(true) {
-                self.css_parser.try_next_token(true);
+        // the value runs up to the next `;` or `}`, a `:` in it usually means the `;` was left out
+        let mut missing_semicolon = false;
+        let mut value = vec![];
+        // `unicode-range` is parsed from the text of its tokens
+        let unicode_range = name.eq_ignore_ascii_case("unicode-range");
+        let mut text = String::new();
+        self.css_parser.current_token(true);
+        loop {
+            match self.css_parser.current_token(false) {
+                Token::Semicolon(..) | Token::RightBrace(..) | Token::EOF => break,
+                Token::Colon(..) => missing_semicolon = true,
+                _ => (),
             }
 
-            if let Token::RightBrace(..) = self.css_parser.current_token(true) {
-                self.css_parser.try_next_token(true);
-                break;
+            if unicode_range {
+                text.push_str(self.css_parser.current_text());
             }
+            value.extend(self.css_parser.parse_component_value());
         }
 
-        properties
+        let important = strip_important(&mut value);
+        if unicode_range {
+            value = value_parser::parse_unicode_ranges(&text).unwrap_or(value);
+        }
+        while value.last().is_some_and(ComponentValue::is_whitespace) {
+            value.pop();
+        }
+
+        // custom properties can be empty and hold just about anything
+        let custom = name.starts_with("--");
+        if value.is_empty() && !custom {
+            let token = self.css_parser.current_token(false);
+            let span = self.css_parser.current_span();
+            self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
+                found: format!("{}", token),
+                expected: Some("value".to_string()),
+                context: None,
+                span,
+            });
+            self.css_parser.skip_declaration();
+            return None;
+        }
+
+        let span = start.to(self.css_parser.previous_span());
+        if missing_semicolon && !custom {
+            self.css_parser.error_handler.warn(ParserWarning::MissingSemicolon(name.clone(), span));
+        }
+        if let Token::Semicolon(..) = self.css_parser.current_token(true) {
+            self.css_parser.try_next_token(true);
+        }
+
+        Some(Property { name, value, important, span })
+    }
+}
+
+// Removes a trailing `!important`, whitespace is allowed around the `!`
+fn strip_important(value: &mut Vec<ComponentValue>) -> bool {
+    let mut tokens = value.iter().enumerate().rev().filter(|(_, value)| !value.is_whitespace());
+    match (tokens.next(), tokens.next()) {
+        (Some((_, ComponentValue::Identifier(important))), Some((bang, ComponentValue::Delim('!'))))
+            if important.eq_ignore_ascii_case("important") => {
+            value.truncate(bang);
+            true
+        },
+        _ => false,
     }
 }
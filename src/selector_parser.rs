@@ -1,54 +1,62 @@
 use crate::parser::{CssParser};
-use crate::lexer::Token;
+use crate::lexer::{Token, HashType, Numeric, NumericType};
 use crate::error::ParserError;
-use crate::style_sheet::{SelectorGroup, Selector};
+use crate::style_sheet::{
+    SelectorGroup, Selector, Combinator, AttributeSelector, AttributeOperation, AttributeMatcher,
+    CaseSensitivity, NamespacePrefix, PseudoClass, PseudoElement, NthKind, AnPlusB, RelativeSelector,
+};
+use crate::span::Span;
 
 pub fn is_token_selector(token: &Token) -> bool {
+    matches!(token, Token::Identifier(..) | Token::Delim('*') | Token::Delim('.') | Token::Hash(_, HashType::Id) | Token::LeftBracket(..)
+        | Token::Colon(..) | Token::Delim('|'))
+}
+
+fn token_combinator(token: &Token) -> Option<Combinator> {
     match token {
-        Token::Identifier(..) | Token::Star(..) | Token::IdSelector(..) | Token::ClassSelector(..) => true,
-        _ => false,
+        Token::Delim('>') => Some(Combinator::Child),
+        Token::Delim('+') => Some(Combinator::NextSibling),
+        Token::Delim('~') => Some(Combinator::SubsequentSibling),
+        _ => None,
     }
 }
 
 // Selector Grammar:
 // selector_list := selector_group[,selector_group..] {
-// selector_group := selector[ selector..]
-// selector := *|[.#]word
-pub struct SelectorParser<'a> {
+// selector_group := selector[ combinator selector..]
+// combinator := whitespace|>|+|~
+// selector := [type].word|#word|[attribute]|:pseudo_class|::pseudo_element..
+// type := [prefix|]word|[prefix|]*
+// attribute := [prefix|]word[ matcher value[ i|s]]
+// pseudo_class := word|function(arguments)
+pub struct SelectorParser<'a, 'i> {
     // TODO had to make some fns and fields public, find alternative
-    css_parser: &'a mut CssParser,
+    css_parser: &'a mut CssParser<'i>,
+    // set on the first error, an invalid selector drops the whole rule
+    invalid: bool,
 }
 
-impl<'a> SelectorParser<'a> {
-    pub fn new(css_parser: &'a mut CssParser) -> SelectorParser {
-        SelectorParser { css_parser }
+impl<'a, 'i> SelectorParser<'a, 'i> {
+    pub fn new(css_parser: &'a mut CssParser<'i>) -> SelectorParser<'a, 'i> {
+        SelectorParser { css_parser, invalid: false }
     }
 
-    pub fn parse(&mut self) -> Vec<SelectorGroup> {
-        self.parse_selectors()
+    pub fn parse(&mut self) -> Option<Vec<SelectorGroup>> {
+        let selectors = self.parse_selectors();
+        if self.invalid {
+            None
+        } else {
+            Some(selectors)
+        }
     }
 
     fn parse_selectors(&mut self) -> Vec<SelectorGroup> {
-        let current = self.css_parser.current_token(true);
-        if !is_token_selector(&current) {
-            self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                found: current.to_string(),
-                expected: Some("identifier".to_string()),
-                context: Some("selector".to_string()),
-            });
-        }
-
         let selectors = self.parse_selector_list();
 
         match self.css_parser.current_token(true) {
             Token::LeftBrace(..) => selectors,
-
             t => {
-                self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                    found: t.to_string(),
-                    expected: Some("{".to_string()),
-                    context: Some("selector".to_string()),
-                });
+                self.flag(&t, Some("{"));
                 selectors
             },
         }
@@ -59,17 +67,8 @@ impl<'a> SelectorParser<'a> {
 
         loop {
             match self.css_parser.current_token(true) {
-                Token::LeftBrace(..) => return selectors,
-
-                Token::EOF => {
-                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: "EOF".to_string(),
-                        expected: None,
-                        context: Some("selector".to_string()),
-                    });
-
-                    return selectors
-                },
+                // `)` ends the list inside functional pseudo-classes
+                Token::LeftBrace(..) | Token::RightBrace(..) | Token::RightParen(..) | Token::EOF => return selectors,
 
                 Token::Comma(..) => {
                     self.css_parser.try_next_token(true);
@@ -81,98 +80,835 @@ impl<'a> SelectorParser<'a> {
         }
     }
 
+    // Stops at `,`, `{`, `}`, `)` or EOF
     fn parse_selector_group(&mut self) -> SelectorGroup {
         let current = self.css_parser.current_token(true);
         if !is_token_selector(&current) {
-            self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                found: current.to_string(),
-                expected: Some("identifier".to_string()),
-                context: Some("selector".to_string()),
-            });
+            self.flag(&current, Some("identifier"));
         }
 
+        let start = self.css_parser.current_span();
         let mut selectors: Vec<Selector> = vec![self.parse_selector()];
+        let mut combinators: Vec<Combinator> = vec![];
 
-        SelectorGroup(loop {
-            match self.css_parser.current_token(false) {
-                Token::Comma(..) => break selectors,
+        loop {
+            let combinator = match self.css_parser.current_token(false) {
+                Token::Comma(..) | Token::LeftBrace(..) | Token::RightBrace(..) | Token::RightParen(..) | Token::EOF => break,
 
-                Token::Whitespace(..) => match self.css_parser.try_next_token(true) {
-                    Token::LeftBrace(..) => break selectors,
-                    _ => selectors.push(self.parse_selector()),
+                Token::Whitespace(..) => {
+                    let next = self.css_parser.try_next_token(true);
+                    match next {
+                        Token::Comma(..) | Token::LeftBrace(..) | Token::RightBrace(..) | Token::RightParen(..)
+                        | Token::EOF => break,
+                        _ => match token_combinator(&next) {
+                            Some(combinator) => {
+                                self.css_parser.try_next_token(true);
+                                combinator
+                            },
+                            None => Combinator::Descendant,
+                        },
+                    }
                 },
 
-                Token::EOF => {
-                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: "EOF".to_string(),
-                        expected: None,
-                        context: Some("selector".to_string()),
-                    });
-
-                    break selectors
+                token => match token_combinator(&token) {
+                    Some(combinator) => {
+                        self.css_parser.try_next_token(true);
+                        combinator
+                    },
+                    None => Combinator::Descendant,
                 },
+            };
 
-                token => {
-                    self.css_parser.try_next_token(false);
-                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: token.to_string(),
-                        expected: None,
-                        context: Some("selector".to_string()),
-                    });
-
-                    continue
-                },
+            // a combinator has to be followed by a compound selector
+            let current = self.css_parser.current_token(true);
+            if !is_token_selector(&current) {
+                self.flag(&current, Some("selector"));
             }
-        })
+
+            combinators.push(combinator);
+            selectors.push(self.parse_selector());
+        }
+
+        SelectorGroup {
+            selectors,
+            combinators,
+            span: start.to(self.css_parser.previous_span()),
+        }
     }
 
     fn parse_selector(&mut self) -> Selector {
+        let start = self.css_parser.current_span();
         let mut selector = Selector {
             id: None,
             tag_name: None,
+            universal: false,
+            namespace: None,
             class_names: vec![],
+            attributes: vec![],
+            pseudo_classes: vec![],
+            pseudo_element: None,
+            span: start,
         };
 
+        // a type selector can only come first
+        if let Token::Identifier(..) | Token::Delim('*') | Token::Delim('|') = self.css_parser.current_token(false) {
+            self.parse_type_selector(&mut selector);
+        }
+
         loop {
             match self.css_parser.current_token(false) {
-                Token::IdSelector(val) => {
-                    selector.id = Some(val);
+                Token::Hash(val, HashType::Id) => {
+                    selector.id = Some(val.into_owned());
                     self.css_parser.try_next_token(false);
                 },
 
-                Token::ClassSelector(val) => {
-                    selector.class_names.push(val);
-                    self.css_parser.try_next_token(false);
+                Token::Delim('.') => match self.css_parser.try_next_token(false) {
+                    Token::Identifier(val) => {
+                        selector.class_names.push(val.into_owned());
+                        self.css_parser.try_next_token(false);
+                    },
+                    t => self.flag(&t, Some("identifier")),
+                },
+
+                Token::LeftBracket(..) => {
+                    if let Some(attribute) = self.parse_attribute_selector() {
+                        selector.attributes.push(attribute);
+                    }
                 },
 
-                Token::Identifier(val) => {
-                    selector.tag_name = Some(val);
+                Token::Colon(..) => self.parse_pseudo(&mut selector),
+
+                Token::LeftBrace(..) | Token::RightBrace(..) | Token::RightParen(..) | Token::Comma(..)
+                | Token::Whitespace(..) | Token::EOF => break,
+                Token::Delim('>') | Token::Delim('+') | Token::Delim('~') => break,
+
+                t => {
+                    self.flag(&t, None);
+                    self.css_parser.skip_component_value();
+                },
+            }
+        }
+
+        // without a prefix only elements in the default namespace match, if there is one
+        if selector.namespace.is_none() {
+            if let Some(url) = self.css_parser.namespaces.get("") {
+                selector.namespace = Some(NamespacePrefix::Default(url.clone()));
+            }
+        }
+
+        selector.span = start.to(self.css_parser.previous_span());
+        selector
+    }
+
+    fn parse_type_selector(&mut self, selector: &mut Selector) {
+        let span = self.css_parser.current_span();
+        // `None` for `*`, either can turn out to be a namespace prefix
+        let name = match self.css_parser.current_token(false) {
+            Token::Identifier(name) => Some(name.into_owned()),
+            Token::Delim('*') => None,
+            Token::Delim('|') => {
+                selector.namespace = Some(NamespacePrefix::None);
+                self.parse_type_name(selector);
+                return;
+            },
+            _ => return,
+        };
+
+        match self.css_parser.try_next_token(false) {
+            Token::Delim('|') => {
+                selector.namespace = match name {
+                    Some(prefix) => self.resolve_prefix(prefix, span),
+                    None => Some(NamespacePrefix::Any),
+                };
+                self.parse_type_name(selector);
+            },
+            _ => match name {
+                Some(name) => selector.tag_name = Some(name),
+                None => selector.universal = true,
+            },
+        }
+    }
+
+    // the current token is the `|` after the prefix
+    fn parse_type_name(&mut self, selector: &mut Selector) {
+        match self.css_parser.try_next_token(false) {
+            Token::Identifier(name) => {
+                selector.tag_name = Some(name.into_owned());
+                self.css_parser.try_next_token(false);
+            },
+            Token::Delim('*') => {
+                selector.universal = true;
+                self.css_parser.try_next_token(false);
+            },
+            t => self.flag(&t, Some("identifier")),
+        }
+    }
+
+    fn resolve_prefix(&mut self, prefix: String, span: Span) -> Option<NamespacePrefix> {
+        match self.css_parser.namespaces.get(&prefix) {
+            Some(url) => {
+                let url = url.clone();
+                Some(NamespacePrefix::Named { prefix, url })
+            },
+            None => {
+                self.flag_error(ParserError::UndeclaredNamespace(prefix, span));
+                None
+            },
+        }
+    }
+
+    // the current token is the first `:`
+    fn parse_pseudo(&mut self, selector: &mut Selector) {
+        let pseudo_element = match self.css_parser.try_next_token(false) {
+            // only one pseudo-element is allowed per compound selector
+            colon @ Token::Colon(..) if selector.pseudo_element.is_some() => {
+                self.flag(&colon, Some("pseudo-class"));
+                return;
+            },
+            // and only user action pseudo-classes can follow it, eg `::before:hover`
+            token @ Token::Identifier(..) | token @ Token::Function(..)
+                if selector.pseudo_element.is_some() && !is_user_action_pseudo_class(&token) => {
+                self.flag(&token, Some("user action pseudo-class"));
+                return;
+            },
+            Token::Colon(..) => match self.css_parser.try_next_token(false) {
+                Token::Identifier(name) => {
                     self.css_parser.try_next_token(false);
+                    Some(PseudoElement { name: name.to_ascii_lowercase(), arguments: None })
+                },
+                Token::Function(name) => {
+                    self.css_parser.try_next_token(false);
+                    let arguments = self.parse_function_arguments();
+                    Some(PseudoElement { name: name.to_ascii_lowercase(), arguments: Some(arguments) })
+                },
+                t => {
+                    self.flag(&t, Some("identifier"));
+                    return;
                 },
+            },
+
+            Token::Identifier(name) => {
+                let name = name.to_ascii_lowercase();
+                self.css_parser.try_next_token(false);
+                match name.as_str() {
+                    // css2 pseudo-elements may be written with a single colon
+                    "before" | "after" | "first-line" | "first-letter" => Some(PseudoElement { name, arguments: None }),
+                    _ => {
+                        selector.pseudo_classes.push(PseudoClass::Named(name));
+                        None
+                    },
+                }
+            },
+
+            Token::Function(name) => {
+                let name = name.to_ascii_lowercase();
+                self.css_parser.try_next_token(true);
+                if let Some(pseudo_class) = self.parse_functional_pseudo_class(name) {
+                    selector.pseudo_classes.push(pseudo_class);
+                }
+                None
+            },
+
+            t => {
+                self.flag(&t, Some("identifier"));
+                return;
+            },
+        };
+
+        if pseudo_element.is_some() {
+            selector.pseudo_element = pseudo_element;
+        }
+    }
+
+    // the current token is the first argument, the closing `)` is consumed
+    fn parse_functional_pseudo_class(&mut self, name: String) -> Option<PseudoClass> {
+        let nth_kind = match name.as_str() {
+            "nth-child" => Some(NthKind::NthChild),
+            "nth-last-child" => Some(NthKind::NthLastChild),
+            "nth-of-type" => Some(NthKind::NthOfType),
+            "nth-last-of-type" => Some(NthKind::NthLastOfType),
+            _ => None,
+        };
+
+        let pseudo_class = match (name.as_str(), nth_kind) {
+            (_, Some(kind)) => self.parse_nth(kind),
+            ("not", _) => Some(PseudoClass::Not(self.parse_selector_list())),
+            ("is", _) | ("matches", _) => Some(PseudoClass::Is(self.parse_selector_list())),
+            ("where", _) => Some(PseudoClass::Where(self.parse_selector_list())),
+            ("has", _) => Some(PseudoClass::Has(self.parse_relative_selector_list())),
+            _ => {
+                let arguments = self.parse_function_arguments();
+                return Some(PseudoClass::Function { name, arguments });
+            },
+        };
+
+        match self.css_parser.current_token(true) {
+            Token::RightParen(..) if pseudo_class.is_some() => {
+                self.css_parser.try_next_token(false);
+                pseudo_class
+            },
+            t => {
+                self.flag(&t, Some(")"));
+                self.skip_to_closing(|token| matches!(token, Token::RightParen(..)));
+                None
+            },
+        }
+    }
+
+    fn parse_nth(&mut self, kind: NthKind) -> Option<PseudoClass> {
+        let an_plus_b = self.parse_an_plus_b()?;
+
+        let of = match self.css_parser.current_token(true) {
+            Token::Identifier(of) if of.eq_ignore_ascii_case("of")
+                && matches!(kind, NthKind::NthChild | NthKind::NthLastChild) => {
+                self.css_parser.try_next_token(true);
+                Some(self.parse_selector_list())
+            },
+            _ => None,
+        };
+
+        Some(PseudoClass::Nth { kind, an_plus_b, of })
+    }
+
+    // An+B micro syntax, `odd`, `even`, `3`, `-n+2`, `2n - 1`, ..
+    fn parse_an_plus_b(&mut self) -> Option<AnPlusB> {
+        let current = self.css_parser.current_token(true);
+        let (a, rest) = match &current {
+            Token::Identifier(value) if value.eq_ignore_ascii_case("odd") => {
+                self.css_parser.try_next_token(true);
+                return Some(AnPlusB { a: 2, b: 1 });
+            },
+            Token::Identifier(value) if value.eq_ignore_ascii_case("even") => {
+                self.css_parser.try_next_token(true);
+                return Some(AnPlusB { a: 2, b: 0 });
+            },
+            Token::Number(number) if number.kind == NumericType::Integer => {
+                self.css_parser.try_next_token(true);
+                return Some(AnPlusB { a: 0, b: clamp_integer(number.value) });
+            },
+            Token::Dimension(number, unit) if number.kind == NumericType::Integer => {
+                (clamp_integer(number.value), unit.to_ascii_lowercase())
+            },
+            Token::Identifier(value) => match value.to_ascii_lowercase() {
+                value if value.starts_with("-n") => (-1, value[1..].to_string()),
+                value => (1, value),
+            },
+            // `+n`, the `n` has to follow the `+` directly
+            Token::Delim('+') => match self.css_parser.try_next_token(false) {
+                Token::Identifier(value) => (1, value.to_ascii_lowercase()),
+                t => {
+                    self.flag(&t, Some("n"));
+                    return None;
+                },
+            },
+            t => {
+                self.flag(t, Some("An+B"));
+                return None;
+            },
+        };
 
-                Token::LeftBrace(..) | Token::Comma(..) | Token::Whitespace(..) => break,
+        let b = match rest.as_str() {
+            "n" => {
+                self.css_parser.try_next_token(true);
+                self.parse_an_plus_b_offset()?
+            },
+            // `n- 1`
+            "n-" => match self.css_parser.try_next_token(true) {
+                Token::Number(number) if is_unsigned_integer(&number) => {
+                    self.css_parser.try_next_token(true);
+                    clamp_integer(-number.value)
+                },
+                t => {
+                    self.flag(&t, Some("integer"));
+                    return None;
+                },
+            },
+            // `n-1` is lexed as a single identifier or dimension unit
+            rest => match rest.strip_prefix("n-").filter(|digits| !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit())) {
+                Some(digits) => {
+                    self.css_parser.try_next_token(true);
+                    clamp_integer(-digits.parse::<f64>().unwrap_or(f64::INFINITY))
+                },
+                None => {
+                    let current = self.css_parser.current_token(true);
+                    self.flag(&current, Some("An+B"));
+                    return None;
+                },
+            },
+        };
+
+        Some(AnPlusB { a, b })
+    }
+
+    // the optional `+ B` or `-B` after `An`
+    fn parse_an_plus_b_offset(&mut self) -> Option<i32> {
+        match self.css_parser.current_token(true) {
+            Token::Number(number) if number.kind == NumericType::Integer && !is_unsigned_integer(&number) => {
+                self.css_parser.try_next_token(true);
+                Some(clamp_integer(number.value))
+            },
+            Token::Delim(sign @ '+') | Token::Delim(sign @ '-') => match self.css_parser.try_next_token(true) {
+                Token::Number(number) if is_unsigned_integer(&number) => {
+                    self.css_parser.try_next_token(true);
+                    Some(clamp_integer(if sign == '-' { -number.value } else { number.value }))
+                },
+                t => {
+                    self.flag(&t, Some("integer"));
+                    None
+                },
+            },
+            _ => Some(0),
+        }
+    }
 
+    fn parse_relative_selector_list(&mut self) -> Vec<RelativeSelector> {
+        let mut selectors = vec![self.parse_relative_selector()];
+
+        while let Token::Comma(..) = self.css_parser.current_token(true) {
+            self.css_parser.try_next_token(true);
+            selectors.push(self.parse_relative_selector());
+        }
+
+        selectors
+    }
+
+    fn parse_relative_selector(&mut self) -> RelativeSelector {
+        let current = self.css_parser.current_token(true);
+        let combinator = match token_combinator(&current) {
+            Some(combinator) => {
+                self.css_parser.try_next_token(true);
+                combinator
+            },
+            None => Combinator::Descendant,
+        };
+
+        RelativeSelector {
+            combinator,
+            selector: self.parse_selector_group(),
+        }
+    }
+
+    // keeps the arguments as written, up to and including the closing `)`
+    fn parse_function_arguments(&mut self) -> String {
+        let mut arguments = String::new();
+        let mut depth = 0;
+
+        loop {
+            match self.css_parser.current_token(false) {
                 Token::EOF => {
-                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: "EOF".to_string(),
-                        expected: None,
-                        context: Some("selector".to_string()),
-                    });
+                    self.flag(&Token::EOF, Some(")"));
+                    break;
+                },
+                Token::RightParen(..) if depth == 0 => {
+                    self.css_parser.try_next_token(false);
+                    break;
+                },
+                Token::Function(..) | Token::LeftParen(..) => depth += 1,
+                Token::RightParen(..) => depth -= 1,
+                _ => (),
+            }
+
+            arguments.push_str(self.css_parser.current_text());
+            self.css_parser.try_next_token(false);
+        }
+
+        arguments.trim().to_string()
+    }
+
+    fn parse_attribute_selector(&mut self) -> Option<AttributeSelector> {
+        let start = self.css_parser.current_span();
+        self.css_parser.try_next_token(true);
+
+        let attribute = self.parse_attribute(start);
+        if attribute.is_none() {
+            self.skip_to_closing(|token| matches!(token, Token::RightBracket(..)));
+        }
+
+        attribute
+    }
+
+    fn parse_attribute(&mut self, start: Span) -> Option<AttributeSelector> {
+        let mut namespace = None;
+        // `[lang|=en]` is only told apart from `[ns|name]` by the token after the `|`
+        let mut matcher = None;
 
-                    break
+        let name = match self.css_parser.current_token(true) {
+            Token::Identifier(name) => {
+                let span = self.css_parser.current_span();
+                let name = name.into_owned();
+                match self.css_parser.try_next_token(false) {
+                    Token::Delim('|') => match self.css_parser.try_next_token(false) {
+                        Token::Identifier(local_name) => {
+                            namespace = Some(self.resolve_prefix(name, span)?);
+                            self.css_parser.try_next_token(false);
+                            local_name.into_owned()
+                        },
+                        Token::Delim('=') => {
+                            matcher = Some(AttributeMatcher::DashMatch);
+                            self.css_parser.try_next_token(true);
+                            name
+                        },
+                        t => {
+                            self.flag(&t, Some("identifier"));
+                            return None;
+                        },
+                    },
+                    _ => name,
+                }
+            },
+            Token::Delim('*') => match self.css_parser.try_next_token(false) {
+                Token::Delim('|') => {
+                    namespace = Some(NamespacePrefix::Any);
+                    self.parse_attribute_local_name()?
+                },
+                t => {
+                    self.flag(&t, Some("|"));
+                    return None;
                 },
+            },
+            Token::Delim('|') => {
+                namespace = Some(NamespacePrefix::None);
+                self.parse_attribute_local_name()?
+            },
+            t => {
+                self.flag(&t, Some("identifier"));
+                return None;
+            },
+        };
 
+        let matcher = match matcher {
+            Some(matcher) => Some(matcher),
+            None => match self.css_parser.current_token(true) {
+                Token::RightBracket(..) => None,
+                Token::Delim('=') => {
+                    self.css_parser.try_next_token(true);
+                    Some(AttributeMatcher::Equals)
+                },
+                Token::Delim(c @ '~') | Token::Delim(c @ '|') | Token::Delim(c @ '^')
+                | Token::Delim(c @ '$') | Token::Delim(c @ '*') => match self.css_parser.try_next_token(false) {
+                    Token::Delim('=') => {
+                        self.css_parser.try_next_token(true);
+                        Some(match c {
+                            '~' => AttributeMatcher::Includes,
+                            '|' => AttributeMatcher::DashMatch,
+                            '^' => AttributeMatcher::Prefix,
+                            '$' => AttributeMatcher::Suffix,
+                            _ => AttributeMatcher::Substring,
+                        })
+                    },
+                    t => {
+                        self.flag(&t, Some("="));
+                        return None;
+                    },
+                },
                 t => {
+                    self.flag(&t, Some("]"));
+                    return None;
+                },
+            },
+        };
+
+        let operation = match matcher {
+            None => None,
+            Some(matcher) => {
+                let value = match self.css_parser.current_token(true) {
+                    Token::Identifier(value) | Token::String(value) => {
+                        self.css_parser.try_next_token(true);
+                        value.into_owned()
+                    },
+                    t => {
+                        self.flag(&t, Some("identifier or string"));
+                        return None;
+                    },
+                };
+
+                let case_sensitivity = match self.css_parser.current_token(true) {
+                    Token::Identifier(flag) if flag.eq_ignore_ascii_case("i") => Some(CaseSensitivity::Insensitive),
+                    Token::Identifier(flag) if flag.eq_ignore_ascii_case("s") => Some(CaseSensitivity::Sensitive),
+                    _ => None,
+                };
+                if case_sensitivity.is_some() {
+                    self.css_parser.try_next_token(true);
+                }
+
+                Some(AttributeOperation { matcher, value, case_sensitivity })
+            },
+        };
+
+        match self.css_parser.current_token(true) {
+            Token::RightBracket(..) => {
+                self.css_parser.try_next_token(false);
+            },
+            t => {
+                self.flag(&t, Some("]"));
+                return None;
+            },
+        }
+
+        Some(AttributeSelector {
+            namespace,
+            name,
+            operation,
+            span: start.to(self.css_parser.previous_span()),
+        })
+    }
+
+    // the current token is the `|` before the name
+    fn parse_attribute_local_name(&mut self) -> Option<String> {
+        match self.css_parser.try_next_token(false) {
+            Token::Identifier(name) => {
+                self.css_parser.try_next_token(false);
+                Some(name.into_owned())
+            },
+            t => {
+                self.flag(&t, Some("identifier"));
+                None
+            },
+        }
+    }
+
+    // skips the rest of an invalid block or function, up to and including its closing token
+    fn skip_to_closing(&mut self, is_closing: fn(&Token) -> bool) {
+        loop {
+            match self.css_parser.current_token(false) {
+                Token::EOF => return,
+                token if is_closing(&token) => {
                     self.css_parser.try_next_token(false);
-                    self.css_parser.error_handler.flag(&ParserError::UnexpectedToken {
-                        found: t.to_string(),
-                        expected: None,
-                        context: Some("selector".to_string()),
-                    });
+                    return;
                 },
+                _ => self.css_parser.skip_component_value(),
             }
         }
+    }
 
-        selector
+    // only the first error is reported since the rest of the rule is dropped anyway
+    fn flag(&mut self, found: &Token, expected: Option<&str>) {
+        let span = self.css_parser.current_span();
+        self.flag_error(ParserError::UnexpectedToken {
+            found: found.to_string(),
+            expected: expected.map(|e| e.to_string()),
+            context: Some("selector".to_string()),
+            span,
+        });
+    }
+
+    fn flag_error(&mut self, error: ParserError) {
+        if self.invalid {
+            return;
+        }
+
+        self.invalid = true;
+        self.css_parser.error_handler.flag(&error);
+    }
+}
+
+fn is_unsigned_integer(number: &Numeric) -> bool {
+    number.kind == NumericType::Integer && !number.representation.starts_with(['+', '-'])
+}
+
+// the pseudo-classes allowed after a pseudo-element
+fn is_user_action_pseudo_class(token: &Token) -> bool {
+    match token {
+        Token::Identifier(name) => {
+            ["hover", "active", "focus", "focus-visible", "focus-within"].iter().any(|n| name.eq_ignore_ascii_case(n))
+        },
+        _ => false,
+    }
+}
+
+// out of range coefficients and offsets saturate, the same way for every way of writing them
+fn clamp_integer(value: f64) -> i32 {
+    value.clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParserError;
+    use crate::style_sheet::{
+        CssRule, PseudoClass, AnPlusB, Combinator, SelectorGroup, Specificity, AttributeMatcher, CaseSensitivity,
+        NamespacePrefix,
+    };
+
+    // the selector groups of the first style rule
+    fn parse_selectors(css: &str) -> Vec<SelectorGroup> {
+        let output = crate::parse_str(css);
+        assert!(output.errors.is_empty(), "{}: {:?}", css, output.errors);
+        output.stylesheet.rules.into_iter().find_map(|rule| match rule {
+            CssRule::Style(rule) => Some(rule.selectors),
+            _ => None,
+        }).unwrap_or_default()
+    }
+
+    fn serialize(css: &str) -> Vec<String> {
+        parse_selectors(css).iter().map(|group| group.to_string()).collect()
+    }
+
+    fn parse_an_plus_b(argument: &str) -> Option<AnPlusB> {
+        let output = crate::parse_str(&format!(":nth-child({}) {{}}", argument));
+        match output.stylesheet.rules.first() {
+            Some(CssRule::Style(rule)) => match rule.selectors[0].selectors[0].pseudo_classes.first() {
+                Some(PseudoClass::Nth { an_plus_b, .. }) => Some(*an_plus_b),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_an_plus_b_forms() {
+        let cases = [
+            ("odd", (2, 1)), ("even", (2, 0)), ("5", (0, 5)), ("-n+3", (-1, 3)), ("+n", (1, 0)), ("2n", (2, 0)),
+            ("2n+1", (2, 1)), ("2n- 1", (2, -1)), ("2n -1", (2, -1)), ("n-1", (1, -1)), ("-2n + 3", (-2, 3)),
+        ];
+        for (argument, (a, b)) in cases {
+            assert_eq!(parse_an_plus_b(argument), Some(AnPlusB { a, b }), "{}", argument);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_an_plus_b() {
+        for argument in ["+ n", "2n + +1", "n-", "3.5", "2n+1.5"] {
+            assert_eq!(parse_an_plus_b(argument), None, "{}", argument);
+        }
+    }
+
+    #[test]
+    fn saturates_out_of_range_an_plus_b() {
+        let max = i32::MAX;
+        let cases = [
+            ("n-99999999999", (1, -max - 1)), ("n- 99999999999", (1, -max - 1)), ("n - 99999999999", (1, -max - 1)),
+            ("99999999999n+99999999999", (max, max)), ("-99999999999", (0, -max - 1)),
+        ];
+        for (argument, (a, b)) in cases {
+            assert_eq!(parse_an_plus_b(argument), Some(AnPlusB { a, b }), "{}", argument);
+        }
+    }
+
+    #[test]
+    fn matches_without_overflow() {
+        let an_plus_b = parse_an_plus_b("n-99999999999").unwrap();
+        assert!(an_plus_b.matches(1));
+        assert!(an_plus_b.matches(i32::MAX));
+        assert!(!AnPlusB { a: i32::MIN, b: i32::MAX }.matches(1));
+        assert!(AnPlusB { a: -1, b: 3 }.matches(3));
+        assert!(!AnPlusB { a: -1, b: 3 }.matches(4));
+        assert!(AnPlusB { a: 2, b: 1 }.matches(5));
+        assert!(!AnPlusB { a: 0, b: 0 }.matches(1));
+    }
+
+    #[test]
+    fn rejects_a_second_pseudo_element() {
+        for css in ["a::before::after {}", "a::before:after {}", "a:first-line::marker {}"] {
+            let output = crate::parse_str(css);
+            assert!(output.stylesheet.rules.is_empty(), "{}", css);
+            assert_eq!(output.errors.len(), 1, "{}", css);
+        }
+    }
+
+    #[test]
+    fn allows_only_user_action_pseudo_classes_after_a_pseudo_element() {
+        for css in ["a::before:hover {}", "a:first-child::after:FOCUS:active {}", "::selection:focus-within {}"] {
+            let output = crate::parse_str(css);
+            assert!(output.errors.is_empty(), "{}: {:?}", css, output.errors);
+            assert_eq!(output.stylesheet.rules.len(), 1, "{}", css);
+        }
+        for css in ["a::before:first-child {}", "a::after:not(.b) {}", "a::before:nth-child(2) {}"] {
+            let output = crate::parse_str(css);
+            assert!(output.stylesheet.rules.is_empty(), "{}", css);
+            assert_eq!(output.errors.len(), 1, "{}", css);
+        }
+    }
+
+    #[test]
+    fn parses_combinators() {
+        let groups = parse_selectors("a>b+c~d e {}");
+        assert_eq!(groups[0].combinators, [
+            Combinator::Child, Combinator::NextSibling, Combinator::SubsequentSibling, Combinator::Descendant,
+        ]);
+        assert_eq!(groups[0].selectors.len(), 5);
+        assert_eq!(groups[0].specificity(), Specificity(0, 0, 5));
+
+        // whitespace around a combinator isn't a descendant combinator
+        assert_eq!(serialize("a  >  b ,c/**/~ d\n\t.e {}"), ["a > b", "c ~ d .e"]);
+        assert_eq!(parse_selectors("#a .b > c {}")[0].specificity(), Specificity(1, 1, 1));
+    }
+
+    #[test]
+    fn rejects_dangling_combinators() {
+        for css in ["> a {}", "a > {}", "a > > b {}", "a + ~ b {}", "a, {}"] {
+            let output = crate::parse_str(css);
+            assert!(output.stylesheet.rules.is_empty(), "{}", css);
+            assert!(!output.errors.is_empty(), "{}", css);
+        }
+    }
+
+    #[test]
+    fn parses_attribute_selectors() {
+        let groups = parse_selectors("[lang|=EN i] {}");
+        let attribute = &groups[0].selectors[0].attributes[0];
+        assert_eq!(attribute.name, "lang");
+        let operation = attribute.operation.as_ref().unwrap();
+        assert_eq!(operation.matcher, AttributeMatcher::DashMatch);
+        assert_eq!(operation.value, "EN");
+        assert_eq!(operation.case_sensitivity, Some(CaseSensitivity::Insensitive));
+
+        let cases = [
+            ("[href]", "[href]"), ("[a=b]", "[a=\"b\"]"), ("[ a ~= 'b c' ]", "[a~=\"b c\"]"),
+            ("[a^=x S]", "[a^=\"x\" s]"), ("[a$=\"y\"]", "[a$=\"y\"]"), ("[a*=z]", "[a*=\"z\"]"),
+            ("[*|a]", "[*|a]"), ("[|b]", "[|b]"), ("[data-\\31=\"\\\"\"]", "[data-1=\"\\\"\"]"),
+        ];
+        for (css, serialized) in cases {
+            assert_eq!(serialize(&format!("{} {{}}", css)), [serialized], "{}", css);
+        }
+    }
+
+    #[test]
+    fn attribute_selectors_count_as_classes() {
+        assert_eq!(parse_selectors("a[href][target=_blank] {}")[0].specificity(), Specificity(0, 2, 1));
+    }
+
+    #[test]
+    fn rejects_malformed_attribute_selectors() {
+        for css in ["[=b] {}", "a[b c] {}", "[a=b x] {}", "[a==b] {}", "[a=1px] {}", "[svg|a] {}"] {
+            let output = crate::parse_str(css);
+            assert!(output.stylesheet.rules.is_empty(), "{}", css);
+            assert!(!output.errors.is_empty(), "{}", css);
+        }
+        let namespaced = parse_selectors("@namespace svg url(x); [svg|href] {}");
+        assert!(matches!(&namespaced[0].selectors[0].attributes[0].namespace, Some(NamespacePrefix::Named { url, .. }) if url == "x"));
+    }
+
+    #[test]
+    fn parses_universal_and_namespaced_type_selectors() {
+        let css = "@namespace svg url(http://www.w3.org/2000/svg); * {} svg|circle {} *|* {} |p {} svg|* {} *.a {}";
+        let output = crate::parse_str(css);
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        let serialized = output.stylesheet.rules.iter().filter_map(|rule| match rule {
+            CssRule::Style(rule) => Some(rule.selectors[0].to_string()),
+            _ => None,
+        });
+        assert_eq!(serialized.collect::<Vec<_>>(), ["*", "svg|circle", "*|*", "|p", "svg|*", "*.a"]);
+
+        let groups = parse_selectors("@namespace svg url(x); svg|circle {}");
+        let selector = &groups[0].selectors[0];
+        assert_eq!(selector.tag_name.as_deref(), Some("circle"));
+        assert!(matches!(&selector.namespace, Some(NamespacePrefix::Named { prefix, url }) if prefix == "svg" && url == "x"));
+        assert_eq!(groups[0].specificity(), Specificity(0, 0, 1));
+        assert_eq!(parse_selectors("*|* {}")[0].specificity(), Specificity::empty());
+    }
+
+    #[test]
+    fn applies_the_default_namespace_to_type_selectors() {
+        let groups = parse_selectors("@namespace url(http://x); p {}");
+        assert_eq!(groups[0].selectors[0].namespace, Some(NamespacePrefix::Default("http://x".to_string())));
+        // but never to attributes
+        assert_eq!(parse_selectors("@namespace url(http://x); [a] {}")[0].selectors[0].attributes[0].namespace, None);
+    }
+
+    #[test]
+    fn rejects_undeclared_namespace_prefixes() {
+        let output = crate::parse_str("svg|circle {} a {}");
+        assert_eq!(output.stylesheet.rules.len(), 1);
+        assert!(matches!(&output.errors[..], [ParserError::UndeclaredNamespace(prefix, _)] if prefix == "svg"));
     }
 }
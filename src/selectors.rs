@@ -0,0 +1,354 @@
+use crate::style_sheet::{
+    SelectorGroup, Selector, Combinator, AttributeSelector, AttributeMatcher, CaseSensitivity, PseudoClass,
+    NthKind, AnPlusB, RelativeSelector,
+};
+
+// The document tree as seen by selector matching, implemented by a cheap handle to an element.
+// Siblings and children are elements only, text nodes are skipped.
+pub trait Element: Sized + Clone + PartialEq {
+    fn parent(&self) -> Option<Self>;
+    fn previous_sibling(&self) -> Option<Self>;
+    fn next_sibling(&self) -> Option<Self>;
+    fn first_child(&self) -> Option<Self>;
+
+    // local name, compared ASCII case-insensitively as in html
+    fn tag_name(&self) -> &str;
+
+    // namespace url, empty for none
+    fn namespace(&self) -> &str {
+        ""
+    }
+
+    fn id(&self) -> Option<&str>;
+    fn has_class(&self, name: &str) -> bool;
+
+    // `namespace` is `None` to look in any namespace and empty for attributes without one
+    fn attribute(&self, namespace: Option<&str>, name: &str) -> Option<&str>;
+
+    // user action and other dynamic states, eg `hover`, `focus`, `checked`
+    fn has_state(&self, state: &str) -> bool;
+
+    // no children, text included
+    fn is_empty(&self) -> bool {
+        self.first_child().is_none()
+    }
+}
+
+// an empty group matches nothing
+pub fn matches<E: Element>(selector: &SelectorGroup, element: &E) -> bool {
+    match selector.selectors.len().checked_sub(1) {
+        Some(last) => matches_complex(selector, last, element, None),
+        None => false,
+    }
+}
+
+// true if any of the selectors match
+pub fn matches_list<E: Element>(selectors: &[SelectorGroup], element: &E) -> bool {
+    selectors.iter().any(|selector| matches(selector, element))
+}
+
+// Matches `selectors[..=index]` right to left. An anchor is the element a `:has()` argument is relative to,
+// the leftmost compound has to be related to it by the given combinator.
+fn matches_complex<E: Element>(
+    selector: &SelectorGroup,
+    index: usize,
+    element: &E,
+    anchor: Option<(&E, Combinator)>,
+) -> bool {
+    if !matches_compound(&selector.selectors[index], element) {
+        return false;
+    }
+
+    if index == 0 {
+        return match anchor {
+            Some((anchor, combinator)) => is_related(element, anchor, combinator),
+            None => true,
+        };
+    }
+
+    let next = |candidate: &E| matches_complex(selector, index - 1, candidate, anchor);
+    match selector.combinators[index - 1] {
+        Combinator::Child => element.parent().is_some_and(|parent| next(&parent)),
+        Combinator::Descendant => ancestors(element).any(|ancestor| next(&ancestor)),
+        Combinator::NextSibling => element.previous_sibling().is_some_and(|sibling| next(&sibling)),
+        Combinator::SubsequentSibling => previous_siblings(element).any(|sibling| next(&sibling)),
+    }
+}
+
+// whether `element` is related to `anchor` as in `anchor <combinator> element`
+fn is_related<E: Element>(element: &E, anchor: &E, combinator: Combinator) -> bool {
+    match combinator {
+        Combinator::Child => element.parent().as_ref() == Some(anchor),
+        Combinator::Descendant => ancestors(element).any(|ancestor| &ancestor == anchor),
+        Combinator::NextSibling => element.previous_sibling().as_ref() == Some(anchor),
+        Combinator::SubsequentSibling => previous_siblings(element).any(|sibling| &sibling == anchor),
+    }
+}
+
+fn matches_compound<E: Element>(selector: &Selector, element: &E) -> bool {
+    // elements are never pseudo-elements
+    if selector.pseudo_element.is_some() {
+        return false;
+    }
+
+    if let Some(url) = selector.namespace.as_ref().and_then(|namespace| namespace.url()) {
+        if element.namespace() != url {
+            return false;
+        }
+    }
+
+    if let Some(tag_name) = &selector.tag_name {
+        if !element.tag_name().eq_ignore_ascii_case(tag_name) {
+            return false;
+        }
+    }
+
+    if let Some(id) = &selector.id {
+        if element.id() != Some(id.as_str()) {
+            return false;
+        }
+    }
+
+    selector.class_names.iter().all(|class_name| element.has_class(class_name))
+        && selector.attributes.iter().all(|attribute| matches_attribute(attribute, element))
+        && selector.pseudo_classes.iter().all(|pseudo_class| matches_pseudo_class(pseudo_class, element))
+}
+
+fn matches_attribute<E: Element>(attribute: &AttributeSelector, element: &E) -> bool {
+    let namespace = match &attribute.namespace {
+        Some(namespace) => namespace.url(),
+        None => Some(""),
+    };
+
+    let value = match element.attribute(namespace, &attribute.name) {
+        Some(value) => value,
+        None => return false,
+    };
+
+    let operation = match &attribute.operation {
+        Some(operation) => operation,
+        None => return true,
+    };
+
+    let (value, expected) = match operation.case_sensitivity {
+        Some(CaseSensitivity::Insensitive) => (value.to_ascii_lowercase(), operation.value.to_ascii_lowercase()),
+        _ => (value.to_string(), operation.value.clone()),
+    };
+
+    match operation.matcher {
+        AttributeMatcher::Equals => value == expected,
+        AttributeMatcher::Includes => {
+            !expected.is_empty()
+                && !expected.contains(char::is_whitespace)
+                && value.split_whitespace().any(|word| word == expected)
+        },
+        AttributeMatcher::DashMatch => {
+            value == expected || (value.starts_with(&expected) && value[expected.len()..].starts_with('-'))
+        },
+        AttributeMatcher::Prefix => !expected.is_empty() && value.starts_with(&expected),
+        AttributeMatcher::Suffix => !expected.is_empty() && value.ends_with(&expected),
+        AttributeMatcher::Substring => !expected.is_empty() && value.contains(&expected),
+    }
+}
+
+fn matches_pseudo_class<E: Element>(pseudo_class: &PseudoClass, element: &E) -> bool {
+    match pseudo_class {
+        PseudoClass::Named(name) => match name.as_str() {
+            // there's no scoping element so `:scope` is the root
+            "root" | "scope" => element.parent().is_none(),
+            "empty" => element.is_empty(),
+            "first-child" => element.previous_sibling().is_none(),
+            "last-child" => element.next_sibling().is_none(),
+            "only-child" => element.previous_sibling().is_none() && element.next_sibling().is_none(),
+            "first-of-type" => !previous_siblings(element).any(|sibling| is_same_type(&sibling, element)),
+            "last-of-type" => !next_siblings(element).any(|sibling| is_same_type(&sibling, element)),
+            "only-of-type" => {
+                !previous_siblings(element).any(|sibling| is_same_type(&sibling, element))
+                    && !next_siblings(element).any(|sibling| is_same_type(&sibling, element))
+            },
+            state => element.has_state(state),
+        },
+        PseudoClass::Nth { kind, an_plus_b, of } => matches_nth(*kind, an_plus_b, of.as_deref(), element),
+        PseudoClass::Not(selectors) => !matches_list(selectors, element),
+        PseudoClass::Is(selectors) | PseudoClass::Where(selectors) => matches_list(selectors, element),
+        PseudoClass::Has(selectors) => selectors.iter().any(|selector| matches_relative(selector, element)),
+        PseudoClass::Function { name, arguments } if name == "lang" => matches_lang(arguments, element),
+        PseudoClass::Function { .. } => false,
+    }
+}
+
+fn matches_nth<E: Element>(kind: NthKind, an_plus_b: &AnPlusB, of: Option<&[SelectorGroup]>, element: &E) -> bool {
+    if let Some(selectors) = of {
+        if !matches_list(selectors, element) {
+            return false;
+        }
+    }
+
+    let counts = |sibling: &E| match kind {
+        NthKind::NthChild | NthKind::NthLastChild => of.as_ref().map_or(true, |selectors| matches_list(selectors, sibling)),
+        NthKind::NthOfType | NthKind::NthLastOfType => is_same_type(sibling, element),
+    };
+
+    let siblings = match kind {
+        NthKind::NthChild | NthKind::NthOfType => previous_siblings(element).filter(counts).count(),
+        NthKind::NthLastChild | NthKind::NthLastOfType => next_siblings(element).filter(counts).count(),
+    };
+
+    an_plus_b.matches(siblings as i32 + 1)
+}
+
+fn matches_relative<E: Element>(selector: &RelativeSelector, anchor: &E) -> bool {
+    let last = match selector.selector.selectors.len().checked_sub(1) {
+        Some(last) => last,
+        None => return false,
+    };
+    let anchored = Some((anchor, selector.combinator));
+
+    // every element the leftmost compound could be, along with everything right of it
+    match selector.combinator {
+        Combinator::Descendant | Combinator::Child => descendants(anchor)
+            .any(|candidate| matches_complex(&selector.selector, last, &candidate, anchored)),
+        Combinator::NextSibling | Combinator::SubsequentSibling => next_siblings(anchor)
+            .flat_map(|sibling| std::iter::once(sibling.clone()).chain(descendants(&sibling)))
+            .any(|candidate| matches_complex(&selector.selector, last, &candidate, anchored)),
+    }
+}
+
+// `:lang(en, "fr-CA")`, the language is inherited from the closest `lang` attribute
+fn matches_lang<E: Element>(arguments: &str, element: &E) -> bool {
+    let language = std::iter::once(element.clone())
+        .chain(ancestors(element))
+        .find_map(|element| element.attribute(Some(""), "lang").map(|lang| lang.to_ascii_lowercase()));
+
+    let language = match language {
+        Some(language) => language,
+        None => return false,
+    };
+
+    arguments.split(',')
+        .map(|range| range.trim().trim_matches(|c| c == '"' || c == '\'').to_ascii_lowercase())
+        .any(|range| range == "*" || language == range || language.starts_with(&format!("{}-", range)))
+}
+
+fn is_same_type<E: Element>(a: &E, b: &E) -> bool {
+    a.tag_name().eq_ignore_ascii_case(b.tag_name()) && a.namespace() == b.namespace()
+}
+
+fn ancestors<E: Element>(element: &E) -> impl Iterator<Item = E> {
+    std::iter::successors(element.parent(), |element| element.parent())
+}
+
+fn previous_siblings<E: Element>(element: &E) -> impl Iterator<Item = E> {
+    std::iter::successors(element.previous_sibling(), |element| element.previous_sibling())
+}
+
+fn next_siblings<E: Element>(element: &E) -> impl Iterator<Item = E> {
+    std::iter::successors(element.next_sibling(), |element| element.next_sibling())
+}
+
+// in document order
+fn descendants<E: Element>(element: &E) -> impl Iterator<Item = E> {
+    let root = element.clone();
+    std::iter::successors(element.first_child(), move |current| {
+        if let Some(child) = current.first_child() {
+            return Some(child);
+        }
+
+        // the next sibling of the closest ancestor that has one, without leaving the subtree
+        let mut current = current.clone();
+        loop {
+            if let Some(sibling) = current.next_sibling() {
+                return Some(sibling);
+            }
+
+            current = current.parent()?;
+            if current == root {
+                return None;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, matches_list};
+    use crate::dom::{Document, NodeId};
+    use crate::span::Span;
+    use crate::style_sheet::{CssRule, SelectorGroup};
+
+    fn parse_selectors(selectors: &str) -> Vec<SelectorGroup> {
+        let output = crate::parse_str(&format!("{} {{}}", selectors));
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        match output.stylesheet.rules.into_iter().next() {
+            Some(CssRule::Style(rule)) => rule.selectors,
+            rule => panic!("not a style rule: {:?}", rule),
+        }
+    }
+
+    // `<ul id="list"><li class="a"/><li/><li class="a"/><li/></ul>`
+    fn list() -> (Document, NodeId, Vec<NodeId>) {
+        let mut document = Document::new();
+        let list = document.append_element(None, "ul");
+        document.set_attribute(list, "id", "list");
+        let items = (0..4).map(|_| document.append_element(Some(list), "li")).collect::<Vec<_>>();
+        document.set_attribute(items[0], "class", "a");
+        document.set_attribute(items[2], "class", "a");
+        (document, list, items)
+    }
+
+    fn matching(document: &Document, items: &[NodeId], selectors: &str) -> Vec<usize> {
+        let selectors = parse_selectors(selectors);
+        (0..items.len()).filter(|&index| matches_list(&selectors, &document.element(items[index]))).collect()
+    }
+
+    #[test]
+    fn matches_combinators() {
+        let (document, _, items) = list();
+        assert_eq!(matching(&document, &items, "#list > li"), [0, 1, 2, 3]);
+        assert_eq!(matching(&document, &items, "ul li.a"), [0, 2]);
+        assert_eq!(matching(&document, &items, ".a + li"), [1, 3]);
+        assert_eq!(matching(&document, &items, ".a ~ li"), [1, 2, 3]);
+        assert_eq!(matching(&document, &items, "ol li"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn matches_structural_pseudo_classes() {
+        let (document, _, items) = list();
+        assert_eq!(matching(&document, &items, "li:nth-child(odd)"), [0, 2]);
+        assert_eq!(matching(&document, &items, "li:nth-last-child(-n+2)"), [2, 3]);
+        assert_eq!(matching(&document, &items, "li:nth-child(2 of .a)"), [2]);
+        assert_eq!(matching(&document, &items, "li:first-child, li:last-child"), [0, 3]);
+        assert_eq!(matching(&document, &items, "li:not(.a)"), [1, 3]);
+        assert_eq!(matching(&document, &items, "li:nth-child(n-99999999999)"), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn matches_has() {
+        let (document, list, _) = list();
+        let matches_list_element = |selectors: &str| matches_list(&parse_selectors(selectors), &document.element(list));
+        assert!(matches_list_element("ul:has(> .a)"));
+        assert!(matches_list_element("ul:has(li + li.a)"));
+        assert!(!matches_list_element("ul:has(> ul)"));
+    }
+
+    #[test]
+    fn empty_group_matches_nothing() {
+        let (document, _, items) = list();
+        let group = SelectorGroup { selectors: vec![], combinators: vec![], span: Span::default() };
+        assert!(!matches(&group, &document.element(items[0])));
+    }
+
+    #[test]
+    fn computes_specificity() {
+        let specificity = |selector: &str| {
+            let specificity = parse_selectors(selector)[0].specificity();
+            (specificity.0, specificity.1, specificity.2)
+        };
+        assert_eq!(specificity("*"), (0, 0, 0));
+        assert_eq!(specificity("ul#list > li.a:hover::before"), (1, 2, 3));
+        assert_eq!(specificity(":is(#a, .b) li"), (1, 0, 1));
+        assert_eq!(specificity(":where(#a) li"), (0, 0, 1));
+        assert_eq!(specificity(":nth-child(2 of #a, .b)"), (1, 1, 0));
+        assert_eq!(specificity(":has(> #a)"), (1, 0, 0));
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::{self, Write};
+
+// Serializes an identifier per CSSOM, escaping whatever would not lex back into the same identifier
+pub(crate) fn write_identifier<W: Write>(dest: &mut W, value: &str) -> fmt::Result {
+    if value == "-" {
+        return dest.write_str("\\-");
+    }
+
+    let starts_with_dash = value.starts_with('-');
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\0' => dest.write_char('\u{FFFD}')?,
+            '\u{1}'..='\u{1F}' | '\u{7F}' => write!(dest, "\\{:x} ", c as u32)?,
+            '0'..='9' if index == 0 || (index == 1 && starts_with_dash) => write!(dest, "\\{:x} ", c as u32)?,
+            '-' | '_' | 'a'..='z' | 'A'..='Z' | '0'..='9' => dest.write_char(c)?,
+            c if c as u32 >= 0x80 => dest.write_char(c)?,
+            c => {
+                dest.write_char('\\')?;
+                dest.write_char(c)?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+// Like an identifier but it may start with a digit or a dash, as in hashes
+pub(crate) fn write_name<W: Write>(dest: &mut W, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '0'..='9' | '-' => dest.write_char(c)?,
+            c => {
+                let mut buffer = [0; 4];
+                write_identifier(dest, c.encode_utf8(&mut buffer))?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+// Serializes a string per CSSOM, always double quoted
+pub(crate) fn write_string<W: Write>(dest: &mut W, value: &str) -> fmt::Result {
+    dest.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '\0' => dest.write_char('\u{FFFD}')?,
+            '\u{1}'..='\u{1F}' | '\u{7F}' => write!(dest, "\\{:x} ", c as u32)?,
+            '"' | '\\' => {
+                dest.write_char('\\')?;
+                dest.write_char(c)?;
+            },
+            c => dest.write_char(c)?,
+        }
+    }
+
+    dest.write_char('"')
+}
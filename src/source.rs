@@ -1,135 +1,106 @@
-use std::io::{BufReader, BufRead};
-use std::fs::File;
-use crate::source::State::Reading;
-
-pub const EOF: &str = "";
+use crate::span::Position;
 
+// Cursor over the input, characters and slices are borrowed from it directly
 #[derive(Debug)]
-enum State {
-    Init,
-    Reading {
-        line: String,
-        position: usize,
-    },
-    Done,
+pub struct Source<'a> {
+    input: &'a str,
+    position: Position,
+    current: Option<char>,
 }
 
-impl State {
-    pub fn eol(&self) -> bool {
-        match self {
-            State::Init => false,
-            State::Done => true,
-            // -1 is a terrible hack, should be fixed
-            State::Reading { line, position } => *position >= line.len() -1,
+impl<'a> Source<'a> {
+    pub fn new(input: &'a str) -> Source<'a> {
+        Source {
+            input,
+            position: Position::start(),
+            current: input.chars().next(),
         }
     }
-}
-
-#[derive(Debug)]
-pub struct Source {
-    state: State,
-    reader: BufReader<File>,
-}
 
-impl Source {
-    pub fn new(reader: BufReader<File>) -> Source {
-        Source {
-            reader,
-            state: State::Init,
-        }
+    // position of the current character, or of the end of input once it's been reached
+    #[inline]
+    pub fn position(&self) -> Position {
+        self.position
     }
 
-    pub fn current_character(&mut self) -> Option<char> {
-        match &self.state {
-            State::Done => None,
-            State::Init => match self.read_line() {
-                Some(ref mut line) => {
-                    self.state = State::Reading {
-                        position: 0,
-                        line: line.to_string()
-                    };
-                    line.chars().next()
-                },
-                None => {
-                    self.state = State::Done;
-                    return None
-                },
-            },
-            State::Reading { position, line } => line[*position..]
-                .chars()
-                .next(),
-        }
+    #[inline]
+    pub fn current_character(&self) -> Option<char> {
+        self.current
     }
 
+    #[inline]
     pub fn next_character(&mut self) -> Option<char> {
-        match &self.state {
-            State::Init => self.current_character(),
-            State::Done => None,
-            State::Reading { line, position } => {
-                if self.state.eol() {
-                    return self.next_line_character();
-                }
-
-                let chars = line[*position..].char_indices();
-                let (offset, current) = chars.skip(1).next().unwrap();
-
-                self.state = Reading {
-                    position: position + offset,
-                    line: line.to_string(),
-                };
-
-                Some(current)
-            },
+        let previous = self.current?;
+        self.position.offset += previous.len_utf8();
+        self.current = self.remaining().chars().next();
+
+        // newlines are `\n`, `\r`, `\r\n` and form feed, with `\r\n` breaking on the `\n`
+        let newline = match previous {
+            '\n' | '\x0C' => true,
+            '\r' => self.current != Some('\n'),
+            _ => false,
+        };
+        if newline {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
         }
+
+        self.current
     }
 
-    pub fn peek_character(&mut self) -> Option<char> {
-        match &self.state {
-            State::Done => None,
-            State::Init => {
-                self.current_character();
-                self.peek_character()
-            },
-            State::Reading { line, position } => {
-                if self.state.eol() {
-                    return None;
-                }
+    #[inline]
+    pub fn peek_character(&self) -> Option<char> {
+        self.peek_nth_character(1)
+    }
 
-                let chars = line[*position..].char_indices();
-                let (_, current) = chars.skip(1).next()?;
+    #[inline]
+    pub fn peek_nth_character(&self, n: usize) -> Option<char> {
+        self.remaining().chars().nth(n)
+    }
 
-                Some(current)
-            },
-        }
+    #[inline]
+    pub fn slice(&self, start: usize, end: usize) -> &'a str {
+        &self.input[start..end]
     }
 
-    fn next_line_character(&mut self) -> Option<char> {
-        match self.read_line() {
-            Some(line) => {
-                self.state = Reading { line, position: 0 };
-                self.current_character()
-            },
-            None => {
-                self.state = State::Done;
-                None
-            },
-        }
+    #[inline]
+    fn remaining(&self) -> &'a str {
+        &self.input[self.position.offset..]
     }
+}
 
-    fn read_line(&mut self) -> Option<String> {
-        match self.state {
-            State::Done => Some(EOF.to_string()),
-            State::Init | State::Reading { .. } => read_line_from_buffer(&mut self.reader),
+#[cfg(test)]
+mod tests {
+    use super::Source;
+
+    // the line and column of every character
+    fn positions(input: &str) -> Vec<(usize, usize)> {
+        let mut source = Source::new(input);
+        let mut positions = vec![];
+        while source.current_character().is_some() {
+            positions.push((source.position().line, source.position().column));
+            source.next_character();
         }
+        positions
     }
-}
 
-fn read_line_from_buffer(reader: &mut BufReader<File>) -> Option<String> {
-    let mut line = String::new();
-    let len = reader.read_line(&mut line).expect("Failed to read line");
-    if len == 0 {
-        return None
+    #[test]
+    fn counts_every_kind_of_newline() {
+        assert_eq!(positions("a\nb"), [(1, 1), (1, 2), (2, 1)]);
+        assert_eq!(positions("a\x0Cb"), [(1, 1), (1, 2), (2, 1)]);
+        assert_eq!(positions("a\rb"), [(1, 1), (1, 2), (2, 1)]);
+        assert_eq!(positions("a\r\nb"), [(1, 1), (1, 2), (1, 3), (2, 1)]);
+        assert_eq!(positions("\r\r\n\n"), [(1, 1), (2, 1), (2, 2), (3, 1)]);
     }
 
-    Some(line)
+    #[test]
+    fn offsets_are_in_bytes() {
+        let mut source = Source::new("é\nx");
+        source.next_character();
+        assert_eq!(source.position().offset, 2);
+        source.next_character();
+        assert_eq!((source.position().line, source.position().column, source.position().offset), (2, 1, 3));
+    }
 }
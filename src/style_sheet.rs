@@ -1,9 +1,27 @@
+use std::fmt;
 use std::ops::Add;
 
+use crate::span::Span;
+use crate::lexer::{HashType, NumericType};
+use crate::serialize::{write_identifier, write_name, write_string};
+use crate::value_parser;
+use crate::color;
+use crate::color_parser;
+use crate::calc;
+use crate::calc_parser;
+use crate::properties;
+
 
 #[derive(Debug)]
 pub struct StyleSheet {
-    pub rules: Vec<Rule>,
+    pub rules: Vec<CssRule>,
+}
+
+
+#[derive(Debug)]
+pub enum CssRule {
+    Style(Rule),
+    At(AtRule),
 }
 
 
@@ -11,42 +29,495 @@ pub struct StyleSheet {
 pub struct Rule {
     pub selectors: Vec<SelectorGroup>,
     pub properties: Vec<Property>,
+    pub span: Span,
+}
+
+
+#[derive(Debug)]
+pub struct AtRule {
+    // name without the `@`, eg `media`
+    pub name: String,
+    // source text between the name and the block or `;`, with whitespace collapsed
+    pub prelude: String,
+    pub block: Option<AtRuleBlock>,
+    pub kind: AtRuleKind,
+    pub span: Span,
+}
+
+
+#[derive(Debug)]
+pub enum AtRuleBlock {
+    Rules(Vec<CssRule>),
+    Declarations(Vec<Property>),
+    Keyframes(Vec<Keyframe>),
+    // the block of an unknown at-rule, kept as source text
+    Raw(String),
+}
+
+
+#[derive(Debug)]
+pub enum AtRuleKind {
+    Media {
+        queries: Vec<String>,
+    },
+    Import {
+        url: String,
+        // `Some("")` for an anonymous `layer`
+        layer: Option<String>,
+        supports: Option<String>,
+        media: Vec<String>,
+    },
+    FontFace,
+    Keyframes {
+        name: String,
+    },
+    Supports {
+        condition: String,
+    },
+    Page {
+        selector: Option<String>,
+    },
+    Namespace {
+        prefix: Option<String>,
+        url: String,
+    },
+    Layer {
+        // dotted layer names, empty for an anonymous layer block
+        names: Vec<String>,
+    },
+    Unknown,
+}
+
+
+#[derive(Debug)]
+pub struct Keyframe {
+    // `from` and `to` are stored as 0 and 100
+    pub percentages: Vec<f32>,
+    pub properties: Vec<Property>,
+    pub span: Span,
 }
 
 
+// A complex selector, `combinators[i]` links `selectors[i]` to `selectors[i + 1]`
 #[derive(Debug)]
-pub struct SelectorGroup(pub Vec<Selector>);
+pub struct SelectorGroup {
+    pub selectors: Vec<Selector>,
+    pub combinators: Vec<Combinator>,
+    pub span: Span,
+}
 
 impl SelectorGroup {
     pub fn specificity(&self) -> Specificity {
-        self.0.iter().fold(
+        self.selectors.iter().fold(
             Specificity::empty(),
             |spec, sel| spec + sel.specificity()
         )
     }
 }
 
+impl fmt::Display for SelectorGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, selector) in self.selectors.iter().enumerate() {
+            if index > 0 {
+                write!(f, "{}", self.combinators[index - 1])?;
+            }
+            write!(f, "{}", selector)?;
+        }
+
+        Ok(())
+    }
+}
 
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Combinator {
+    // `a b`
+    Descendant,
+    // `a > b`
+    Child,
+    // `a + b`
+    NextSibling,
+    // `a ~ b`
+    SubsequentSibling,
+}
+
+impl fmt::Display for Combinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Combinator::Descendant => " ",
+            Combinator::Child => " > ",
+            Combinator::NextSibling => " + ",
+            Combinator::SubsequentSibling => " ~ ",
+        })
+    }
+}
+
+
+// A compound selector
 #[derive(Debug)]
 pub struct Selector {
     pub id: Option<String>,
+    // `None` for `*` or when there's no type selector
     pub tag_name: Option<String>,
+    // `*`, written or implied by a namespace prefix
+    pub universal: bool,
+    pub namespace: Option<NamespacePrefix>,
     pub class_names: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>,
+    pub pseudo_element: Option<PseudoElement>,
+    pub span: Span,
 }
 
 impl Selector {
+    // Selectors Level 4 specificity of the compound selector
     pub fn specificity(&self) -> Specificity {
-        Specificity(
+        let own = Specificity(
             if self.id.is_some() { 1 } else { 0 },
-            self.class_names.iter().count() as u32,
-            if self.tag_name.is_some() { 1 } else { 0 },
-        )
+            (self.class_names.len() + self.attributes.len()) as u32,
+            self.tag_name.iter().count() as u32 + self.pseudo_element.iter().count() as u32,
+        );
+
+        self.pseudo_classes.iter().fold(own, |spec, pseudo_class| spec + pseudo_class.specificity())
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(namespace) = &self.namespace {
+            write!(f, "{}", namespace)?;
+        }
+        match &self.tag_name {
+            Some(tag_name) => write_identifier(f, tag_name)?,
+            None if self.universal => f.write_str("*")?,
+            None => (),
+        }
+        if let Some(id) = &self.id {
+            f.write_str("#")?;
+            write_identifier(f, id)?;
+        }
+        for class_name in &self.class_names {
+            f.write_str(".")?;
+            write_identifier(f, class_name)?;
+        }
+        for attribute in &self.attributes {
+            write!(f, "{}", attribute)?;
+        }
+        for pseudo_class in &self.pseudo_classes {
+            write!(f, "{}", pseudo_class)?;
+        }
+        if let Some(pseudo_element) = &self.pseudo_element {
+            write!(f, "{}", pseudo_element)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[derive(Debug)]
+pub enum PseudoClass {
+    // `:hover`, `:first-child`, .. names are lowercased
+    Named(String),
+    // `:nth-child(An+B of S)` and the rest of the `:nth-*` family, only `:nth-child` and
+    // `:nth-last-child` take a selector list
+    Nth {
+        kind: NthKind,
+        an_plus_b: AnPlusB,
+        of: Option<Vec<SelectorGroup>>,
+    },
+    Not(Vec<SelectorGroup>),
+    Is(Vec<SelectorGroup>),
+    Where(Vec<SelectorGroup>),
+    Has(Vec<RelativeSelector>),
+    // any other functional pseudo-class, eg `:lang(en)`, arguments are kept as written
+    Function {
+        name: String,
+        arguments: String,
+    },
+}
+
+impl PseudoClass {
+    pub fn specificity(&self) -> Specificity {
+        match self {
+            // the most specific argument counts instead of the pseudo-class itself
+            PseudoClass::Not(selectors) | PseudoClass::Is(selectors) => max_specificity(selectors),
+            PseudoClass::Has(selectors) => selectors.iter()
+                .map(|relative| relative.selector.specificity())
+                .max()
+                .unwrap_or_else(Specificity::empty),
+            PseudoClass::Where(..) => Specificity::empty(),
+            PseudoClass::Nth { of: Some(selectors), .. } => Specificity(0, 1, 0) + max_specificity(selectors),
+            PseudoClass::Named(..) | PseudoClass::Nth { .. } | PseudoClass::Function { .. } => Specificity(0, 1, 0),
+        }
+    }
+}
+
+fn max_specificity(selectors: &[SelectorGroup]) -> Specificity {
+    selectors.iter()
+        .map(|selector| selector.specificity())
+        .max()
+        .unwrap_or_else(Specificity::empty)
+}
+
+impl fmt::Display for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PseudoClass::Named(name) => {
+                f.write_str(":")?;
+                write_identifier(f, name)
+            },
+            PseudoClass::Nth { kind, an_plus_b, of } => {
+                write!(f, ":{}({}", kind, an_plus_b)?;
+                if let Some(selectors) = of {
+                    f.write_str(" of ")?;
+                    write_list(f, selectors)?;
+                }
+                f.write_str(")")
+            },
+            PseudoClass::Not(selectors) => write_function(f, ":not", selectors),
+            PseudoClass::Is(selectors) => write_function(f, ":is", selectors),
+            PseudoClass::Where(selectors) => write_function(f, ":where", selectors),
+            PseudoClass::Has(selectors) => write_function(f, ":has", selectors),
+            PseudoClass::Function { name, arguments } => {
+                f.write_str(":")?;
+                write_identifier(f, name)?;
+                write!(f, "({})", arguments)
+            },
+        }
+    }
+}
+
+fn write_function<T: fmt::Display>(f: &mut fmt::Formatter, name: &str, arguments: &[T]) -> fmt::Result {
+    write!(f, "{}(", name)?;
+    write_list(f, arguments)?;
+    f.write_str(")")
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NthKind {
+    NthChild,
+    NthLastChild,
+    NthOfType,
+    NthLastOfType,
+}
+
+impl fmt::Display for NthKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            NthKind::NthChild => "nth-child",
+            NthKind::NthLastChild => "nth-last-child",
+            NthKind::NthOfType => "nth-of-type",
+            NthKind::NthLastOfType => "nth-last-of-type",
+        })
     }
 }
 
+// Matches every index `a * n + b` for n >= 0, indices start at 1
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AnPlusB {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl AnPlusB {
+    // in i64 so saturated coefficients can't overflow
+    pub fn matches(&self, index: i32) -> bool {
+        let (a, b, index) = (self.a as i64, self.b as i64, index as i64);
+        match a {
+            0 => index == b,
+            a => {
+                let n = index - b;
+                n % a == 0 && n / a >= 0
+            },
+        }
+    }
+}
 
+impl fmt::Display for AnPlusB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.a {
+            0 => return write!(f, "{}", self.b),
+            1 => f.write_str("n")?,
+            -1 => f.write_str("-n")?,
+            a => write!(f, "{}n", a)?,
+        }
+
+        match self.b {
+            0 => Ok(()),
+            b if b > 0 => write!(f, "+{}", b),
+            b => write!(f, "{}", b),
+        }
+    }
+}
+
+// A selector in `:has()`, relative to the element being matched
 #[derive(Debug)]
-pub struct Specificity(u32, u32, u32);
+pub struct RelativeSelector {
+    pub combinator: Combinator,
+    pub selector: SelectorGroup,
+}
+
+impl fmt::Display for RelativeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.combinator {
+            Combinator::Descendant => (),
+            combinator => write!(f, "{} ", combinator.to_string().trim())?,
+        }
+
+        write!(f, "{}", self.selector)
+    }
+}
+
+// `::before`, `::part(label)`, .. the legacy `:before` form is parsed into this as well
+#[derive(Debug)]
+pub struct PseudoElement {
+    pub name: String,
+    pub arguments: Option<String>,
+}
+
+impl fmt::Display for PseudoElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("::")?;
+        write_identifier(f, &self.name)?;
+        match &self.arguments {
+            Some(arguments) => write!(f, "({})", arguments),
+            None => Ok(()),
+        }
+    }
+}
+
+
+// `[name]` or `[name <matcher> value <case sensitivity>]`
+#[derive(Debug)]
+pub struct AttributeSelector {
+    pub namespace: Option<NamespacePrefix>,
+    pub name: String,
+    pub operation: Option<AttributeOperation>,
+    pub span: Span,
+}
+
+impl fmt::Display for AttributeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        if let Some(namespace) = &self.namespace {
+            write!(f, "{}", namespace)?;
+        }
+        write_identifier(f, &self.name)?;
+
+        if let Some(operation) = &self.operation {
+            write!(f, "{}", operation.matcher)?;
+            write_string(f, &operation.value)?;
+            match operation.case_sensitivity {
+                Some(CaseSensitivity::Insensitive) => f.write_str(" i")?,
+                Some(CaseSensitivity::Sensitive) => f.write_str(" s")?,
+                None => (),
+            }
+        }
+
+        f.write_str("]")
+    }
+}
+
+#[derive(Debug)]
+pub struct AttributeOperation {
+    pub matcher: AttributeMatcher,
+    pub value: String,
+    pub case_sensitivity: Option<CaseSensitivity>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AttributeMatcher {
+    // `=`
+    Equals,
+    // `~=`, a whitespace separated list contains the value
+    Includes,
+    // `|=`, equal to the value or starts with it followed by `-`
+    DashMatch,
+    // `^=`
+    Prefix,
+    // `$=`
+    Suffix,
+    // `*=`
+    Substring,
+}
+
+impl fmt::Display for AttributeMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AttributeMatcher::Equals => "=",
+            AttributeMatcher::Includes => "~=",
+            AttributeMatcher::DashMatch => "|=",
+            AttributeMatcher::Prefix => "^=",
+            AttributeMatcher::Suffix => "$=",
+            AttributeMatcher::Substring => "*=",
+        })
+    }
+}
+
+// The `i` and `s` flags, without one the document language decides
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CaseSensitivity {
+    Sensitive,
+    Insensitive,
+}
+
+// The part before `|` in a qualified name, prefixes are resolved against the `@namespace` rules
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NamespacePrefix {
+    // `|name`, no namespace
+    None,
+    // `*|name`, any namespace
+    Any,
+    // `prefix|name`
+    Named {
+        prefix: String,
+        url: String,
+    },
+    // no prefix written but a default namespace is declared, never applies to attributes
+    Default(String),
+}
+
+impl NamespacePrefix {
+    // the namespace url elements have to be in, `None` for any namespace
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            NamespacePrefix::None => Some(""),
+            NamespacePrefix::Any => None,
+            NamespacePrefix::Named { url, .. } | NamespacePrefix::Default(url) => Some(url),
+        }
+    }
+}
+
+impl fmt::Display for NamespacePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NamespacePrefix::None => f.write_str("|"),
+            NamespacePrefix::Any => f.write_str("*|"),
+            NamespacePrefix::Named { prefix, .. } => {
+                write_identifier(f, prefix)?;
+                f.write_str("|")
+            },
+            NamespacePrefix::Default(..) => Ok(()),
+        }
+    }
+}
+
+
+// (ids, classes, types), compared in that order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Specificity(pub u32, pub u32, pub u32);
 
 impl Specificity {
     pub fn empty() -> Specificity {
@@ -54,6 +525,12 @@ impl Specificity {
     }
 }
 
+impl fmt::Display for Specificity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.0, self.1, self.2)
+    }
+}
+
 impl Add for Specificity {
     type Output = Specificity;
 
@@ -70,12 +547,1147 @@ impl Add for Specificity {
 #[derive(Debug)]
 pub struct Property {
     pub name: String,
-    pub value: DataType,
+    // without the surrounding whitespace and `!important`
+    pub value: Vec<ComponentValue>,
+    // ends with `!important`
+    pub important: bool,
+    pub span: Span,
 }
 
+impl Property {
+    // named and system colors are only colors for the properties that take one
+    pub fn typed_value(&self) -> Option<DataType> {
+        if properties::takes_color(&self.name) {
+            if let Some(color) = Color::parse(&self.value) {
+                return Some(DataType::Color(color));
+            }
+        }
+        DataType::parse(&self.value)
+    }
+}
 
-#[derive(Debug)]
+
+// A preserved token, or a block or function with its contents
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentValue {
+    Identifier(String),
+    AtKeyword(String),
+    Hash(String, HashType),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(Number),
+    Percentage(Number),
+    Dimension(Number, String),
+    UnicodeRange(u32, u32),
+    Whitespace,
+    CDO,
+    CDC,
+    Colon,
+    Semicolon,
+    Comma,
+    // closing tokens without a matching opening one
+    RightBracket,
+    RightParen,
+    RightBrace,
+    Block(BlockKind, Vec<ComponentValue>),
+    Function(String, Vec<ComponentValue>),
+}
+
+impl ComponentValue {
+    pub fn is_whitespace(&self) -> bool {
+        matches!(self, ComponentValue::Whitespace)
+    }
+}
+
+impl fmt::Display for ComponentValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComponentValue::Identifier(value) => write_identifier(f, value),
+            ComponentValue::AtKeyword(value) => {
+                f.write_str("@")?;
+                write_identifier(f, value)
+            },
+            ComponentValue::Hash(value, _) => {
+                f.write_str("#")?;
+                write_name(f, value)
+            },
+            ComponentValue::String(value) => write_string(f, value),
+            ComponentValue::Url(value) => {
+                f.write_str("url(")?;
+                write_string(f, value)?;
+                f.write_str(")")
+            },
+            ComponentValue::BadString | ComponentValue::BadUrl => Ok(()),
+            ComponentValue::Delim(c) => write!(f, "{}", c),
+            ComponentValue::Number(number) => write!(f, "{}", number),
+            ComponentValue::Percentage(number) => write!(f, "{}%", number),
+            ComponentValue::Dimension(number, unit) => {
+                write!(f, "{}", number)?;
+                // a unit like `e3` would read back as an exponent
+                let exponent = unit.strip_prefix(['e', 'E'])
+                    .map(|rest| rest.strip_prefix(['+', '-']).unwrap_or(rest))
+                    .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+                match exponent {
+                    true => {
+                        write!(f, "\\{:x} ", unit.as_bytes()[0])?;
+                        write_name(f, &unit[1..])
+                    },
+                    false => write_identifier(f, unit),
+                }
+            },
+            ComponentValue::UnicodeRange(start, end) if start == end => write!(f, "U+{:X}", start),
+            ComponentValue::UnicodeRange(start, end) => write!(f, "U+{:X}-{:X}", start, end),
+            ComponentValue::Whitespace => f.write_str(" "),
+            ComponentValue::CDO => f.write_str("<!--"),
+            ComponentValue::CDC => f.write_str("-->"),
+            ComponentValue::Colon => f.write_str(":"),
+            ComponentValue::Semicolon => f.write_str(";"),
+            ComponentValue::Comma => f.write_str(","),
+            ComponentValue::RightBracket => f.write_str("]"),
+            ComponentValue::RightParen => f.write_str(")"),
+            ComponentValue::RightBrace => f.write_str("}"),
+            ComponentValue::Block(kind, values) => {
+                let (open, close) = match kind {
+                    BlockKind::Parenthesis => ("(", ")"),
+                    BlockKind::Bracket => ("[", "]"),
+                    BlockKind::Brace => ("{", "}"),
+                };
+                f.write_str(open)?;
+                write_values(f, values)?;
+                f.write_str(close)
+            },
+            ComponentValue::Function(name, arguments) => {
+                write_identifier(f, name)?;
+                f.write_str("(")?;
+                write_values(f, arguments)?;
+                f.write_str(")")
+            },
+        }
+    }
+}
+
+fn write_values(f: &mut fmt::Formatter, values: &[ComponentValue]) -> fmt::Result {
+    for value in values {
+        write!(f, "{}", value)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlockKind {
+    // `(..)`
+    Parenthesis,
+    // `[..]`
+    Bracket,
+    // `{..}`
+    Brace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number {
+    pub value: f64,
+    pub kind: NumericType,
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            NumericType::Integer => write!(f, "{}", self.value as i64),
+            // `1.0` rather than `1`, which would read back as an integer. Very large and small
+            // values get an exponent, which keeps them numbers too.
+            NumericType::Number => write!(f, "{:?}", self.value),
+        }
+    }
+}
+
+
+// Typed values, parsed from component values on demand
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
     Keyword(String),
-    // ColorKeyword(String),
+    // a quoted string, eg a font family name like `"Open Sans"`
+    String(String),
+    Length(f32, LengthUnit),
+    Percentage(f32),
+    Number(f32),
+    Integer(i32),
+    Angle(f32, AngleUnit),
+    Time(f32, TimeUnit),
+    Frequency(f32, FrequencyUnit),
+    Resolution(f32, ResolutionUnit),
+    Color(Color),
+    // a math function that couldn't be simplified down to a single value, eg `calc(100% - 2rem)`
+    Calc(CalcNode),
+}
+
+impl DataType {
+    pub fn parse(values: &[ComponentValue]) -> Option<DataType> {
+        value_parser::parse_data_type(values)
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::Keyword(keyword) => write_identifier(f, keyword),
+            DataType::String(value) => write_string(f, value),
+            DataType::Length(value, unit) => write!(f, "{}{}", value, unit),
+            DataType::Percentage(value) => write!(f, "{}%", value),
+            DataType::Number(value) => write!(f, "{}", value),
+            DataType::Integer(value) => write!(f, "{}", value),
+            DataType::Angle(value, unit) => write!(f, "{}{}", value, unit),
+            DataType::Time(value, unit) => write!(f, "{}{}", value, unit),
+            DataType::Frequency(value, unit) => write!(f, "{}{}", value, unit),
+            DataType::Resolution(value, unit) => write!(f, "{}{}", value, unit),
+            DataType::Color(color) => write!(f, "{}", color),
+            // anything but a math function needs a `calc()` around it
+            DataType::Calc(node @ CalcNode::Function(..)) => write!(f, "{}", node),
+            DataType::Calc(node) => write!(f, "calc({})", node),
+        }
+    }
+}
+
+// Defines a unit enum along with its lowercase names, units are matched ASCII case-insensitively
+macro_rules! units {
+    ($name:ident { $($unit:ident => $text:literal,)* }) => {
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        pub enum $name {
+            $($unit,)*
+        }
+
+        impl $name {
+            pub fn from_name(name: &str) -> Option<$name> {
+                $(if name.eq_ignore_ascii_case($text) {
+                    return Some($name::$unit);
+                })*
+
+                None
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($name::$unit => $text,)*
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+units!(LengthUnit {
+    // absolute
+    Px => "px",
+    Cm => "cm",
+    Mm => "mm",
+    Q => "q",
+    In => "in",
+    Pt => "pt",
+    Pc => "pc",
+    // font-relative
+    Em => "em",
+    Rem => "rem",
+    Ex => "ex",
+    Rex => "rex",
+    Cap => "cap",
+    Rcap => "rcap",
+    Ch => "ch",
+    Rch => "rch",
+    Ic => "ic",
+    Ric => "ric",
+    Lh => "lh",
+    Rlh => "rlh",
+    // viewport-percentage, default, small, large and dynamic
+    Vw => "vw",
+    Vh => "vh",
+    Vi => "vi",
+    Vb => "vb",
+    Vmin => "vmin",
+    Vmax => "vmax",
+    Svw => "svw",
+    Svh => "svh",
+    Svi => "svi",
+    Svb => "svb",
+    Svmin => "svmin",
+    Svmax => "svmax",
+    Lvw => "lvw",
+    Lvh => "lvh",
+    Lvi => "lvi",
+    Lvb => "lvb",
+    Lvmin => "lvmin",
+    Lvmax => "lvmax",
+    Dvw => "dvw",
+    Dvh => "dvh",
+    Dvi => "dvi",
+    Dvb => "dvb",
+    Dvmin => "dvmin",
+    Dvmax => "dvmax",
+    // container query
+    Cqw => "cqw",
+    Cqh => "cqh",
+    Cqi => "cqi",
+    Cqb => "cqb",
+    Cqmin => "cqmin",
+    Cqmax => "cqmax",
+});
+
+impl LengthUnit {
+    // the size of an absolute unit in pixels, `None` for relative units
+    pub fn to_px(self) -> Option<f32> {
+        match self {
+            LengthUnit::Px => Some(1.0),
+            LengthUnit::Cm => Some(96.0 / 2.54),
+            LengthUnit::Mm => Some(96.0 / 25.4),
+            LengthUnit::Q => Some(96.0 / 101.6),
+            LengthUnit::In => Some(96.0),
+            LengthUnit::Pt => Some(96.0 / 72.0),
+            LengthUnit::Pc => Some(16.0),
+            _ => None,
+        }
+    }
+
+    pub fn is_absolute(self) -> bool {
+        self.to_px().is_some()
+    }
+}
+
+units!(AngleUnit {
+    Deg => "deg",
+    Grad => "grad",
+    Rad => "rad",
+    Turn => "turn",
+});
+
+impl AngleUnit {
+    pub fn to_degrees(self) -> f32 {
+        match self {
+            AngleUnit::Deg => 1.0,
+            AngleUnit::Grad => 0.9,
+            AngleUnit::Rad => 180.0 / std::f32::consts::PI,
+            AngleUnit::Turn => 360.0,
+        }
+    }
+}
+
+units!(TimeUnit {
+    S => "s",
+    Ms => "ms",
+});
+
+impl TimeUnit {
+    pub fn to_seconds(self) -> f32 {
+        match self {
+            TimeUnit::S => 1.0,
+            TimeUnit::Ms => 0.001,
+        }
+    }
+}
+
+units!(FrequencyUnit {
+    Hz => "hz",
+    Khz => "khz",
+});
+
+impl FrequencyUnit {
+    pub fn to_hertz(self) -> f32 {
+        match self {
+            FrequencyUnit::Hz => 1.0,
+            FrequencyUnit::Khz => 1000.0,
+        }
+    }
+}
+
+units!(ResolutionUnit {
+    Dpi => "dpi",
+    Dpcm => "dpcm",
+    Dppx => "dppx",
+    // alias of `dppx`
+    X => "x",
+});
+
+impl ResolutionUnit {
+    pub fn to_dppx(self) -> f32 {
+        match self {
+            ResolutionUnit::Dpi => 1.0 / 96.0,
+            ResolutionUnit::Dpcm => 2.54 / 96.0,
+            ResolutionUnit::Dppx | ResolutionUnit::X => 1.0,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    // `currentcolor`, the value of the `color` property
+    CurrentColor,
+    Absolute(AbsoluteColor),
+    // `color-mix()` and relative colors are only kept when they depend on `currentcolor`,
+    // otherwise they're computed right away
+    Mix(Box<ColorMix>),
+    Relative(Box<RelativeColor>),
+}
+
+impl Color {
+    pub fn parse(values: &[ComponentValue]) -> Option<Color> {
+        color_parser::parse_color(values)
+    }
+
+    // `current_color` is what `currentcolor` stands for
+    pub fn resolve(&self, current_color: &AbsoluteColor) -> AbsoluteColor {
+        match self {
+            Color::CurrentColor => *current_color,
+            Color::Absolute(color) => *color,
+            Color::Mix(mix) => mix.resolve(current_color),
+            Color::Relative(relative) => relative.resolve(current_color),
+        }
+    }
+
+    // `None` when the color depends on `currentcolor`
+    pub fn to_rgba(&self) -> Option<Rgba> {
+        match self {
+            Color::Absolute(color) => Some(color.to_rgba()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::CurrentColor => f.write_str("currentcolor"),
+            Color::Absolute(color) => write!(f, "{}", color),
+            Color::Mix(mix) => write!(f, "{}", mix),
+            Color::Relative(relative) => write!(f, "{}", relative),
+        }
+    }
+}
+
+// `color-mix(in <space> <hue> hue, <color> <percentage>, <color> <percentage>)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorMix {
+    pub space: ColorSpace,
+    pub hue_interpolation: HueInterpolation,
+    // percentages from 0 to 100, `None` when left out
+    pub colors: [(Color, Option<f32>); 2],
+}
+
+impl ColorMix {
+    pub fn resolve(&self, current_color: &AbsoluteColor) -> AbsoluteColor {
+        let [(first, first_percentage), (second, second_percentage)] = &self.colors;
+        color::mix(
+            self.space,
+            self.hue_interpolation,
+            (&first.resolve(current_color), *first_percentage),
+            (&second.resolve(current_color), *second_percentage),
+        ).unwrap_or_else(|| AbsoluteColor::new(ColorSpace::Srgb, [0.0; 3], 0.0))
+    }
+}
+
+impl fmt::Display for ColorMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "color-mix(in {}", self.space)?;
+        if self.hue_interpolation != HueInterpolation::Shorter {
+            write!(f, " {} hue", self.hue_interpolation)?;
+        }
+        for (color, percentage) in &self.colors {
+            write!(f, ", {}", color)?;
+            if let Some(percentage) = percentage {
+                write!(f, " {}%", percentage)?;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HueInterpolation {
+    Shorter,
+    Longer,
+    Increasing,
+    Decreasing,
+}
+
+impl HueInterpolation {
+    pub fn from_name(name: &str) -> Option<HueInterpolation> {
+        match name.to_ascii_lowercase().as_str() {
+            "shorter" => Some(HueInterpolation::Shorter),
+            "longer" => Some(HueInterpolation::Longer),
+            "increasing" => Some(HueInterpolation::Increasing),
+            "decreasing" => Some(HueInterpolation::Decreasing),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HueInterpolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HueInterpolation::Shorter => "shorter",
+            HueInterpolation::Longer => "longer",
+            HueInterpolation::Increasing => "increasing",
+            HueInterpolation::Decreasing => "decreasing",
+        })
+    }
+}
+
+// A relative color such as `rgb(from currentcolor r g b / 50%)`, the arguments after the origin
+// are kept as written and parsed again once the origin is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelativeColor {
+    // the lowercased function name, eg `rgb` or `color`
+    pub function: String,
+    pub origin: Color,
+    pub arguments: Vec<ComponentValue>,
+}
+
+impl RelativeColor {
+    pub fn resolve(&self, current_color: &AbsoluteColor) -> AbsoluteColor {
+        let origin = self.origin.resolve(current_color);
+        // the arguments were checked when the color was parsed
+        color_parser::parse_relative(&self.function, &origin, &self.arguments).unwrap_or(origin)
+    }
+}
+
+impl fmt::Display for RelativeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(from {} ", self.function, self.origin)?;
+        write_values(f, &self.arguments)?;
+        f.write_str(")")
+    }
+}
+
+// A color in a given color space. Hex colors, named colors and `rgb()` are in sRGB with components
+// from 0 to 1, the others keep the ranges of their functions, eg `hsl()` saturation goes up to 100.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbsoluteColor {
+    pub space: ColorSpace,
+    // `None` for a missing component, written as `none`
+    pub components: [Option<f32>; 3],
+    pub alpha: Option<f32>,
+    // written as a hex or named color, or with `rgb()`, `hsl()` or `hwb()`, these serialize as `rgb()`
+    pub legacy: bool,
+}
+
+impl AbsoluteColor {
+    pub fn new(space: ColorSpace, components: [f32; 3], alpha: f32) -> AbsoluteColor {
+        AbsoluteColor {
+            space,
+            components: [Some(components[0]), Some(components[1]), Some(components[2])],
+            alpha: Some(alpha),
+            legacy: false,
+        }
+    }
+
+    // an sRGB color in the legacy syntax, as hex and named colors are
+    pub fn rgb(components: [f32; 3], alpha: f32) -> AbsoluteColor {
+        AbsoluteColor { legacy: true, ..AbsoluteColor::new(ColorSpace::Srgb, components, alpha) }
+    }
+
+    // missing components are zero, as when the color is rendered
+    pub fn values(&self) -> [f32; 3] {
+        self.components.map(|component| component.unwrap_or(0.0))
+    }
+
+    // Missing components are zero, except for powerless hues of achromatic colors which become
+    // missing. Out of gamut colors aren't mapped.
+    pub fn to_space(&self, space: ColorSpace) -> AbsoluteColor {
+        let components = color::convert(self.space, self.values(), space);
+        AbsoluteColor {
+            space,
+            components: components.map(|component| Some(component).filter(|component| !component.is_nan())),
+            alpha: self.alpha,
+            legacy: false,
+        }
+    }
+
+    // sRGB components, out of gamut colors aren't clipped
+    pub fn to_srgb(&self) -> [f32; 3] {
+        self.to_space(ColorSpace::Srgb).values()
+    }
+
+    pub fn is_in_gamut(&self, space: ColorSpace) -> bool {
+        color::is_in_gamut(&self.to_space(space))
+    }
+
+    // into the gamut of an RGB space with the CSS Color 4 algorithm, other spaces have no gamut
+    pub fn map_to_gamut(&self, space: ColorSpace) -> AbsoluteColor {
+        color::map_to_gamut(self, space)
+    }
+
+    // `amount` of `other` from 0 to 1, interpolated in `space` as `color-mix()` does
+    pub fn mix(&self, other: &AbsoluteColor, amount: f32, space: ColorSpace, hue: HueInterpolation) -> AbsoluteColor {
+        let amount = amount.clamp(0.0, 1.0) * 100.0;
+        color::mix(space, hue, (self, Some(100.0 - amount)), (other, Some(amount))).unwrap_or(*self)
+    }
+
+    // gamut mapped to sRGB
+    pub fn to_rgba(&self) -> Rgba {
+        self.map_to_gamut(ColorSpace::Srgb).clipped_rgba()
+    }
+
+    fn clipped_rgba(&self) -> Rgba {
+        let [red, green, blue] = self.to_srgb().map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        Rgba { red, green, blue, alpha: self.alpha.unwrap_or(0.0).clamp(0.0, 1.0) }
+    }
+}
+
+impl fmt::Display for AbsoluteColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_component = |f: &mut fmt::Formatter, component: Option<f32>| match component {
+            Some(value) => write!(f, "{}", value),
+            None => f.write_str("none"),
+        };
+
+        // the legacy syntaxes serialize as `rgb()`, as do `hsl()` and `hwb()` colors
+        if self.legacy || matches!(self.space, ColorSpace::Hsl | ColorSpace::Hwb) {
+            return write!(f, "{}", self.clipped_rgba());
+        }
+
+        match self.space.function_name() {
+            Some(name) => write!(f, "{}(", name)?,
+            None => write!(f, "color({} ", self.space)?,
+        }
+        for (index, component) in self.components.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write_component(f, *component)?;
+        }
+        if self.alpha != Some(1.0) {
+            f.write_str(" / ")?;
+            write_component(f, self.alpha)?;
+        }
+        f.write_str(")")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    SrgbLinear,
+    DisplayP3,
+    A98Rgb,
+    ProphotoRgb,
+    Rec2020,
+    XyzD50,
+    XyzD65,
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+    Hsl,
+    Hwb,
+}
+
+impl ColorSpace {
+    // the names used by `color()`, `xyz` is an alias of `xyz-d65`
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        match name.to_ascii_lowercase().as_str() {
+            "srgb" => Some(ColorSpace::Srgb),
+            "srgb-linear" => Some(ColorSpace::SrgbLinear),
+            "display-p3" => Some(ColorSpace::DisplayP3),
+            "a98-rgb" => Some(ColorSpace::A98Rgb),
+            "prophoto-rgb" => Some(ColorSpace::ProphotoRgb),
+            "rec2020" => Some(ColorSpace::Rec2020),
+            "xyz-d50" => Some(ColorSpace::XyzD50),
+            "xyz" | "xyz-d65" => Some(ColorSpace::XyzD65),
+            "lab" => Some(ColorSpace::Lab),
+            "lch" => Some(ColorSpace::Lch),
+            "oklab" => Some(ColorSpace::Oklab),
+            "oklch" => Some(ColorSpace::Oklch),
+            "hsl" => Some(ColorSpace::Hsl),
+            "hwb" => Some(ColorSpace::Hwb),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::SrgbLinear => "srgb-linear",
+            ColorSpace::DisplayP3 => "display-p3",
+            ColorSpace::A98Rgb => "a98-rgb",
+            ColorSpace::ProphotoRgb => "prophoto-rgb",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::XyzD50 => "xyz-d50",
+            ColorSpace::XyzD65 => "xyz-d65",
+            ColorSpace::Lab => "lab",
+            ColorSpace::Lch => "lch",
+            ColorSpace::Oklab => "oklab",
+            ColorSpace::Oklch => "oklch",
+            ColorSpace::Hsl => "hsl",
+            ColorSpace::Hwb => "hwb",
+        }
+    }
+
+    // spaces with their own function, the rest are written with `color()`
+    fn function_name(self) -> Option<&'static str> {
+        match self {
+            ColorSpace::Lab | ColorSpace::Lch | ColorSpace::Oklab | ColorSpace::Oklch
+            | ColorSpace::Hsl | ColorSpace::Hwb => Some(self.name()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// An sRGB color with 8-bit channels, as used for rendering
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    // from 0 to 1
+    pub alpha: f32,
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.alpha == 1.0 {
+            write!(f, "rgb({}, {}, {})", self.red, self.green, self.blue)
+        } else {
+            write!(f, "rgba({}, {}, {}, {})", self.red, self.green, self.blue, self.alpha)
+        }
+    }
+}
+
+
+// A calculation tree of a math function such as `calc()`, `min()` or `sin()`. `calc()` itself
+// isn't kept, `a - b` is a sum with a negated `b` and `a / b` a product with an inverted `b`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcNode {
+    Number(f32),
+    Percentage(f32),
+    Length(f32, LengthUnit),
+    Angle(f32, AngleUnit),
+    Time(f32, TimeUnit),
+    Frequency(f32, FrequencyUnit),
+    Resolution(f32, ResolutionUnit),
+    Sum(Vec<CalcNode>),
+    Product(Vec<CalcNode>),
+    Negate(Box<CalcNode>),
+    Invert(Box<CalcNode>),
+    Function(MathFunction, Vec<CalcNode>),
+}
+
+impl CalcNode {
+    // a math function, type checked and simplified
+    pub fn parse(values: &[ComponentValue]) -> Option<CalcNode> {
+        calc_parser::parse_math_function(values)
+    }
+
+    // `None` if the types of the calculation don't add up, eg `1px + 2s`
+    pub fn calc_type(&self) -> Option<CalcType> {
+        calc::calc_type(self)
+    }
+
+    // folds whatever can be computed without knowing the element, eg `1in + 2px` to `98px`
+    pub fn simplify(self) -> CalcNode {
+        calc::simplify(self)
+    }
+
+    // The value in the canonical unit of its type: px, deg, s, hz or dppx. `None` when there's no
+    // basis to resolve a percentage against.
+    pub fn evaluate(&self, context: &CalcContext) -> Option<f32> {
+        calc::evaluate(self, context)
+    }
+
+    // a leaf with a negative value flipped to positive, for writing sums with `-`
+    fn negated_leaf(&self) -> Option<CalcNode> {
+        let negated = match *self {
+            CalcNode::Number(value) if value < 0.0 => CalcNode::Number(-value),
+            CalcNode::Percentage(value) if value < 0.0 => CalcNode::Percentage(-value),
+            CalcNode::Length(value, unit) if value < 0.0 => CalcNode::Length(-value, unit),
+            CalcNode::Angle(value, unit) if value < 0.0 => CalcNode::Angle(-value, unit),
+            CalcNode::Time(value, unit) if value < 0.0 => CalcNode::Time(-value, unit),
+            CalcNode::Frequency(value, unit) if value < 0.0 => CalcNode::Frequency(-value, unit),
+            CalcNode::Resolution(value, unit) if value < 0.0 => CalcNode::Resolution(-value, unit),
+            _ => return None,
+        };
+        Some(negated)
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        !matches!(
+            self,
+            CalcNode::Sum(..) | CalcNode::Product(..) | CalcNode::Negate(..) | CalcNode::Invert(..) | CalcNode::Function(..)
+        )
+    }
+}
+
+impl fmt::Display for CalcNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // nested sums and products need parentheses
+        let write_operand = |f: &mut fmt::Formatter, node: &CalcNode| match node {
+            CalcNode::Sum(..) | CalcNode::Product(..) => write!(f, "({})", node),
+            node => write!(f, "{}", node),
+        };
+
+        match self {
+            CalcNode::Number(value) => write_calc_number(f, *value),
+            CalcNode::Percentage(value) => write_calc_dimension(f, *value, "%"),
+            CalcNode::Length(value, unit) => write_calc_dimension(f, *value, unit),
+            CalcNode::Angle(value, unit) => write_calc_dimension(f, *value, unit),
+            CalcNode::Time(value, unit) => write_calc_dimension(f, *value, unit),
+            CalcNode::Frequency(value, unit) => write_calc_dimension(f, *value, unit),
+            CalcNode::Resolution(value, unit) => write_calc_dimension(f, *value, unit),
+            CalcNode::Sum(children) => {
+                for (index, child) in children.iter().enumerate() {
+                    match child {
+                        CalcNode::Negate(child) if index > 0 => {
+                            f.write_str(" - ")?;
+                            write_operand(f, child)?;
+                        },
+                        // a negated leaf was folded into its value
+                        child if index > 0 => match child.negated_leaf() {
+                            Some(leaf) => write!(f, " - {}", leaf)?,
+                            None => {
+                                f.write_str(" + ")?;
+                                write_operand(f, child)?;
+                            },
+                        },
+                        child => write_operand(f, child)?,
+                    }
+                }
+                Ok(())
+            },
+            CalcNode::Product(children) => {
+                for (index, child) in children.iter().enumerate() {
+                    match child {
+                        CalcNode::Invert(child) if index > 0 => {
+                            f.write_str(" / ")?;
+                            write_operand(f, child)?;
+                        },
+                        child => {
+                            if index > 0 {
+                                f.write_str(" * ")?;
+                            }
+                            write_operand(f, child)?;
+                        },
+                    }
+                }
+                Ok(())
+            },
+            CalcNode::Negate(child) => {
+                f.write_str("-1 * ")?;
+                write_operand(f, child)
+            },
+            CalcNode::Invert(child) => {
+                f.write_str("1 / ")?;
+                write_operand(f, child)
+            },
+            CalcNode::Function(function, arguments) => {
+                write!(f, "{}(", function.name())?;
+                if let MathFunction::Round(strategy) = function {
+                    if *strategy != RoundingStrategy::Nearest {
+                        write!(f, "{}, ", strategy)?;
+                    }
+                }
+                write_list(f, arguments)?;
+                f.write_str(")")
+            },
+        }
+    }
+}
+
+// `infinity * 1px`, a unit can't follow a constant
+fn write_calc_dimension(f: &mut fmt::Formatter, value: f32, unit: impl fmt::Display) -> fmt::Result {
+    if value.is_finite() {
+        write!(f, "{}{}", value, unit)
+    } else {
+        write_calc_number(f, value)?;
+        write!(f, " * 1{}", unit)
+    }
+}
+
+// the constants for numbers that can't be written as is
+fn write_calc_number(f: &mut fmt::Formatter, value: f32) -> fmt::Result {
+    if value.is_nan() {
+        f.write_str("NaN")
+    } else if value == f32::INFINITY {
+        f.write_str("infinity")
+    } else if value == f32::NEG_INFINITY {
+        f.write_str("-infinity")
+    } else {
+        write!(f, "{}", value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MathFunction {
+    Min,
+    Max,
+    Clamp,
+    Round(RoundingStrategy),
+    Mod,
+    Rem,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Pow,
+    Sqrt,
+    Hypot,
+    Log,
+    Exp,
+    Abs,
+    Sign,
+}
+
+impl MathFunction {
+    // `round()` is `round(nearest, ..)` until its arguments are parsed
+    pub fn from_name(name: &str) -> Option<MathFunction> {
+        match name.to_ascii_lowercase().as_str() {
+            "min" => Some(MathFunction::Min),
+            "max" => Some(MathFunction::Max),
+            "clamp" => Some(MathFunction::Clamp),
+            "round" => Some(MathFunction::Round(RoundingStrategy::Nearest)),
+            "mod" => Some(MathFunction::Mod),
+            "rem" => Some(MathFunction::Rem),
+            "sin" => Some(MathFunction::Sin),
+            "cos" => Some(MathFunction::Cos),
+            "tan" => Some(MathFunction::Tan),
+            "asin" => Some(MathFunction::Asin),
+            "acos" => Some(MathFunction::Acos),
+            "atan" => Some(MathFunction::Atan),
+            "atan2" => Some(MathFunction::Atan2),
+            "pow" => Some(MathFunction::Pow),
+            "sqrt" => Some(MathFunction::Sqrt),
+            "hypot" => Some(MathFunction::Hypot),
+            "log" => Some(MathFunction::Log),
+            "exp" => Some(MathFunction::Exp),
+            "abs" => Some(MathFunction::Abs),
+            "sign" => Some(MathFunction::Sign),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MathFunction::Min => "min",
+            MathFunction::Max => "max",
+            MathFunction::Clamp => "clamp",
+            MathFunction::Round(..) => "round",
+            MathFunction::Mod => "mod",
+            MathFunction::Rem => "rem",
+            MathFunction::Sin => "sin",
+            MathFunction::Cos => "cos",
+            MathFunction::Tan => "tan",
+            MathFunction::Asin => "asin",
+            MathFunction::Acos => "acos",
+            MathFunction::Atan => "atan",
+            MathFunction::Atan2 => "atan2",
+            MathFunction::Pow => "pow",
+            MathFunction::Sqrt => "sqrt",
+            MathFunction::Hypot => "hypot",
+            MathFunction::Log => "log",
+            MathFunction::Exp => "exp",
+            MathFunction::Abs => "abs",
+            MathFunction::Sign => "sign",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RoundingStrategy {
+    // half way rounds up
+    Nearest,
+    Up,
+    Down,
+    ToZero,
+}
+
+impl RoundingStrategy {
+    pub fn from_name(name: &str) -> Option<RoundingStrategy> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" => Some(RoundingStrategy::Nearest),
+            "up" => Some(RoundingStrategy::Up),
+            "down" => Some(RoundingStrategy::Down),
+            "to-zero" => Some(RoundingStrategy::ToZero),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RoundingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RoundingStrategy::Nearest => "nearest",
+            RoundingStrategy::Up => "up",
+            RoundingStrategy::Down => "down",
+            RoundingStrategy::ToZero => "to-zero",
+        })
+    }
+}
+
+// The type of a calculation per CSS Values 4, eg `1px * 1px` has a length exponent of 2
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CalcType {
+    // indexed by `BaseType`
+    pub exponents: [i8; 6],
+    // what the percentages resolve against once they're added to another type, eg length in `100% - 2px`
+    pub percent_hint: Option<BaseType>,
+}
+
+impl CalcType {
+    pub fn number() -> CalcType {
+        CalcType { exponents: [0; 6], percent_hint: None }
+    }
+
+    pub fn of(base_type: BaseType) -> CalcType {
+        let mut exponents = [0; 6];
+        exponents[base_type as usize] = 1;
+        CalcType { exponents, percent_hint: None }
+    }
+
+    pub fn is_number(&self) -> bool {
+        self.exponents == [0; 6]
+    }
+
+    // the type a math function can resolve to, a number or a single base type
+    pub fn base_type(&self) -> Option<BaseType> {
+        BaseType::ALL.iter().copied().find(|&base_type| *self == CalcType { percent_hint: self.percent_hint, ..CalcType::of(base_type) })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BaseType {
+    Length,
+    Angle,
+    Time,
+    Frequency,
+    Resolution,
+    Percent,
+}
+
+impl BaseType {
+    pub const ALL: [BaseType; 6] = [
+        BaseType::Length, BaseType::Angle, BaseType::Time, BaseType::Frequency, BaseType::Resolution, BaseType::Percent,
+    ];
+}
+
+// What relative units and percentages resolve against when a calculation is evaluated, in px
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalcContext {
+    pub font_size: f32,
+    pub root_font_size: f32,
+    pub line_height: f32,
+    pub root_line_height: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    // the size of the nearest query container, container units fall back to the viewport without one
+    pub container_size: Option<(f32, f32)>,
+    // what 100% stands for, percentages can't be resolved without it
+    pub percentage_basis: Option<f32>,
+}
+
+impl CalcContext {
+    // for the root element, `normal` line heights are taken as 1.2
+    pub fn new(font_size: f32, viewport_width: f32, viewport_height: f32) -> CalcContext {
+        CalcContext {
+            font_size,
+            root_font_size: font_size,
+            line_height: font_size * 1.2,
+            root_line_height: font_size * 1.2,
+            viewport_width,
+            viewport_height,
+            container_size: None,
+            percentage_basis: None,
+        }
+    }
+
+    // Horizontal writing is assumed for the logical units. Font metrics aren't known so `ex` and
+    // `ch` are half an `em`, and `cap` is 0.7 of one.
+    pub fn length_to_px(&self, value: f32, unit: LengthUnit) -> f32 {
+        if let Some(px) = unit.to_px() {
+            return value * px;
+        }
+
+        let (vw, vh) = (self.viewport_width / 100.0, self.viewport_height / 100.0);
+        let (cqw, cqh) = self.container_size.map_or((vw, vh), |(width, height)| (width / 100.0, height / 100.0));
+        value * match unit {
+            LengthUnit::Em | LengthUnit::Ic => self.font_size,
+            LengthUnit::Rem | LengthUnit::Ric => self.root_font_size,
+            LengthUnit::Ex | LengthUnit::Ch => self.font_size * 0.5,
+            LengthUnit::Rex | LengthUnit::Rch => self.root_font_size * 0.5,
+            LengthUnit::Cap => self.font_size * 0.7,
+            LengthUnit::Rcap => self.root_font_size * 0.7,
+            LengthUnit::Lh => self.line_height,
+            LengthUnit::Rlh => self.root_line_height,
+            LengthUnit::Vw | LengthUnit::Vi | LengthUnit::Svw | LengthUnit::Svi | LengthUnit::Lvw
+            | LengthUnit::Lvi | LengthUnit::Dvw | LengthUnit::Dvi => vw,
+            LengthUnit::Vh | LengthUnit::Vb | LengthUnit::Svh | LengthUnit::Svb | LengthUnit::Lvh
+            | LengthUnit::Lvb | LengthUnit::Dvh | LengthUnit::Dvb => vh,
+            LengthUnit::Vmin | LengthUnit::Svmin | LengthUnit::Lvmin | LengthUnit::Dvmin => vw.min(vh),
+            LengthUnit::Vmax | LengthUnit::Svmax | LengthUnit::Lvmax | LengthUnit::Dvmax => vw.max(vh),
+            LengthUnit::Cqw | LengthUnit::Cqi => cqw,
+            LengthUnit::Cqh | LengthUnit::Cqb => cqh,
+            LengthUnit::Cqmin => cqw.min(cqh),
+            LengthUnit::Cqmax => cqw.max(cqh),
+            // absolute units are handled above
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ComponentValue;
+    use crate::lexer::NumericType;
+
+    fn serialize(values: &[ComponentValue]) -> String {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn kinds(values: &[ComponentValue]) -> Vec<NumericType> {
+        values.iter()
+            .filter_map(|value| match value {
+                ComponentValue::Number(number) | ComponentValue::Percentage(number) | ComponentValue::Dimension(number, _) => {
+                    Some(number.kind)
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn numbers_keep_their_type_when_serialized() {
+        for input in ["1.0", "1", "-0.0", "+2.50", "1e3", "1e21", "1.5e-7", "3.0px", "10.0%", "2e3em", "1000.0e0"] {
+            let values = crate::parse_component_values(input);
+            let serialized = serialize(&values);
+            let reparsed = crate::parse_component_values(&serialized);
+            assert_eq!(kinds(&reparsed), kinds(&values), "{} serialized as {}", input, serialized);
+            assert_eq!(reparsed, values, "{} serialized as {}", input, serialized);
+        }
+    }
+
+    #[test]
+    fn serializes_component_values() {
+        for (input, expected) in [
+            ("a(b, [c] {d})", "a(b, [c] {d})"),
+            ("1.0px 2", "1.0px 2"),
+            ("#-x", "#-x"),
+            ("1e3\\65 m", "1000.0em"),
+            ("1\\65 3", "1\\65 3"),
+            ("\"a\\\"b\"", "\"a\\\"b\""),
+        ] {
+            assert_eq!(serialize(&crate::parse_component_values(input)), expected, "{}", input);
+        }
+    }
 }
@@ -0,0 +1,195 @@
+use crate::lexer::NumericType;
+use crate::calc_parser;
+use crate::style_sheet::{
+    ComponentValue, DataType, Number, Color, CalcNode, LengthUnit, AngleUnit, TimeUnit, FrequencyUnit, ResolutionUnit,
+};
+
+// Typed parsing of declaration values, `None` if the value isn't one of the supported types. A
+// bare identifier is a keyword even if it names a color, only the property can tell which it is.
+pub fn parse_data_type(values: &[ComponentValue]) -> Option<DataType> {
+    match trim_whitespace(values) {
+        [ComponentValue::Identifier(value)] => Some(DataType::Keyword(value.clone())),
+        [ComponentValue::String(value)] => Some(DataType::String(value.clone())),
+        [ComponentValue::Number(number)] => Some(parse_number(number)),
+        [ComponentValue::Percentage(number)] => Some(DataType::Percentage(number.value as f32)),
+        [ComponentValue::Dimension(number, unit)] => parse_dimension(number.value as f32, unit),
+        [ComponentValue::Function(name, _)] if calc_parser::is_math_function(name) => {
+            CalcNode::parse(values).map(parse_calculation)
+        },
+        _ => Color::parse(values).map(DataType::Color),
+    }
+}
+
+// A calculation that simplified down to a single value is typed as that value, unless it's
+// infinite or NaN which only a calculation can hold
+fn parse_calculation(node: CalcNode) -> DataType {
+    let finite = !matches!(
+        node,
+        CalcNode::Number(value) | CalcNode::Percentage(value) | CalcNode::Length(value, _) | CalcNode::Angle(value, _)
+        | CalcNode::Time(value, _) | CalcNode::Frequency(value, _) | CalcNode::Resolution(value, _) if !value.is_finite()
+    );
+    if !finite {
+        return DataType::Calc(node);
+    }
+
+    match node {
+        CalcNode::Number(value) => DataType::Number(value),
+        CalcNode::Percentage(value) => DataType::Percentage(value),
+        CalcNode::Length(value, unit) => DataType::Length(value, unit),
+        CalcNode::Angle(value, unit) => DataType::Angle(value, unit),
+        CalcNode::Time(value, unit) => DataType::Time(value, unit),
+        CalcNode::Frequency(value, unit) => DataType::Frequency(value, unit),
+        CalcNode::Resolution(value, unit) => DataType::Resolution(value, unit),
+        node => DataType::Calc(node),
+    }
+}
+
+fn parse_number(number: &Number) -> DataType {
+    match number.kind {
+        NumericType::Integer => DataType::Integer(number.value.clamp(i32::MIN as f64, i32::MAX as f64) as i32),
+        NumericType::Number => DataType::Number(number.value as f32),
+    }
+}
+
+// unknown units aren't an error here, the value just isn't typed
+fn parse_dimension(value: f32, unit: &str) -> Option<DataType> {
+    if let Some(unit) = LengthUnit::from_name(unit) {
+        Some(DataType::Length(value, unit))
+    } else if let Some(unit) = AngleUnit::from_name(unit) {
+        Some(DataType::Angle(value, unit))
+    } else if let Some(unit) = TimeUnit::from_name(unit) {
+        Some(DataType::Time(value, unit))
+    } else if let Some(unit) = FrequencyUnit::from_name(unit) {
+        Some(DataType::Frequency(value, unit))
+    } else {
+        ResolutionUnit::from_name(unit).map(|unit| DataType::Resolution(value, unit))
+    }
+}
+
+// The `unicode-range` descriptor, a comma separated list of `<urange>`. These are matched against
+// the text of the tokens rather than the tokens themselves, `u+1e3` is a number token for one.
+pub fn parse_unicode_ranges(text: &str) -> Option<Vec<ComponentValue>> {
+    let mut values = vec![];
+    for range in text.split(',') {
+        if !values.is_empty() {
+            values.extend([ComponentValue::Comma, ComponentValue::Whitespace]);
+        }
+        let (start, end) = parse_unicode_range(range.trim_matches(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')))?;
+        values.push(ComponentValue::UnicodeRange(start, end));
+    }
+    Some(values)
+}
+
+// `U+26`, `U+0-7F` or `U+4??` from https://www.w3.org/TR/css-syntax-3/#urange-syntax
+fn parse_unicode_range(text: &str) -> Option<(u32, u32)> {
+    if !text.get(..2)?.eq_ignore_ascii_case("u+") {
+        return None;
+    }
+    let hex = |digits: &str| match digits.len() {
+        1..=6 if digits.chars().all(|c| c.is_ascii_hexdigit()) => u32::from_str_radix(digits, 16).ok(),
+        _ => None,
+    };
+
+    let (start, end) = match text[2..].split_once('-') {
+        Some((start, end)) => (hex(start)?, hex(end)?),
+        None => {
+            // trailing `?` wildcards stand for any hex digit
+            let digits = text[2..].trim_end_matches('?');
+            let wildcards = text.len() - 2 - digits.len();
+            if wildcards == 0 {
+                (hex(digits)?, hex(digits)?)
+            } else if digits.len() + wildcards <= 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                let start = u32::from_str_radix(&format!("{}{}", digits, "0".repeat(wildcards)), 16).ok()?;
+                let end = u32::from_str_radix(&format!("{}{}", digits, "F".repeat(wildcards)), 16).ok()?;
+                (start, end)
+            } else {
+                return None;
+            }
+        },
+    };
+
+    (start <= end && end <= 0x10FFFF).then_some((start, end))
+}
+
+pub fn trim_whitespace(values: &[ComponentValue]) -> &[ComponentValue] {
+    let start = values.iter().position(|value| !value.is_whitespace()).unwrap_or(values.len());
+    let end = values.iter().rposition(|value| !value.is_whitespace()).map_or(start, |end| end + 1);
+
+    &values[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_data_type;
+    use crate::style_sheet::{CssRule, AtRuleBlock, ComponentValue, DataType, Color, LengthUnit};
+
+    fn parse(value: &str) -> Option<DataType> {
+        parse_data_type(&crate::parse_component_values(value))
+    }
+
+    fn typed_value(declaration: &str) -> Option<DataType> {
+        match crate::parse_str(&format!("p {{ {} }}", declaration)).stylesheet.rules.first() {
+            Some(CssRule::Style(rule)) => rule.properties[0].typed_value(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn bare_identifiers_are_keywords() {
+        for keyword in ["mark", "red", "Tan", "currentcolor", "transparent"] {
+            assert_eq!(parse(keyword).map(|value| value.to_string()), Some(keyword.to_string()));
+        }
+        assert!(matches!(parse("#ff0"), Some(DataType::Color(..))));
+        assert!(matches!(parse("rgb(1 2 3)"), Some(DataType::Color(..))));
+    }
+
+    #[test]
+    fn strings_keep_their_quotes() {
+        assert!(matches!(parse("\"Open Sans\""), Some(DataType::String(value)) if value == "Open Sans"));
+        assert_eq!(parse("\"Open Sans\"").map(|value| value.to_string()), Some("\"Open Sans\"".to_string()));
+        assert_eq!(parse("'a\"b'").map(|value| value.to_string()), Some("\"a\\\"b\"".to_string()));
+        assert!(matches!(parse("Open"), Some(DataType::Keyword(..))));
+    }
+
+    #[test]
+    fn named_colors_are_colors_where_the_property_takes_one() {
+        assert!(matches!(typed_value("color: mark"), Some(DataType::Color(Color::Absolute(..)))));
+        assert!(matches!(typed_value("border-left-color: currentColor"), Some(DataType::Color(Color::CurrentColor))));
+        assert!(matches!(typed_value("font-family: Tan"), Some(DataType::Keyword(..))));
+        assert!(matches!(typed_value("animation-name: red"), Some(DataType::Keyword(..))));
+        assert!(matches!(typed_value("color: inherit"), Some(DataType::Keyword(..))));
+    }
+
+    #[test]
+    fn parses_dimensions() {
+        assert!(matches!(parse("1.5rem"), Some(DataType::Length(value, LengthUnit::Rem)) if value == 1.5));
+        assert!(matches!(parse("3"), Some(DataType::Integer(3))));
+        assert!(matches!(parse("3.0"), Some(DataType::Number(..))));
+        assert!(matches!(parse("50%"), Some(DataType::Percentage(value)) if value == 50.0));
+        assert!(parse("1foo").is_none());
+    }
+
+    #[test]
+    fn parses_unicode_ranges_from_the_descriptor_text() {
+        let css = "@font-face { unicode-range: U+0025-00FF, u+4??, U+1e3; font-family: x }";
+        let output = crate::parse_str(css);
+        let properties = match output.stylesheet.rules.first() {
+            Some(CssRule::At(rule)) => match &rule.block {
+                Some(AtRuleBlock::Declarations(properties)) => properties,
+                block => panic!("unexpected block {:?}", block),
+            },
+            rule => panic!("unexpected rule {:?}", rule),
+        };
+        let ranges = properties[0].value.iter().filter_map(|value| match value {
+            ComponentValue::UnicodeRange(start, end) => Some((*start, *end)),
+            _ => None,
+        });
+        assert_eq!(ranges.collect::<Vec<_>>(), [(0x25, 0xFF), (0x400, 0x4FF), (0x1E3, 0x1E3)]);
+
+        assert!(super::parse_unicode_ranges("U+1?2").is_none());
+        assert!(super::parse_unicode_ranges("U+110000").is_none());
+        assert!(super::parse_unicode_ranges("U+5-1").is_none());
+        assert!(super::parse_unicode_ranges("U+0000000").is_none());
+        assert!(super::parse_unicode_ranges("U+0-7F garbage").is_none());
+    }
+}
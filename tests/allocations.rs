@@ -0,0 +1,42 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts heap allocations so tokenizing can be checked to borrow from the input
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<F: FnOnce()>(run: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    run();
+    ALLOCATIONS.load(Ordering::SeqCst) - before
+}
+
+// one test only, the counter is shared by every thread
+#[test]
+fn tokenizing_borrows_from_the_input() {
+    let input = ".btn-primary > a:hover, #main { color: #06c; margin: 0 auto -.5em 1e3px; }\n".repeat(1000);
+    let count = |input: &str| allocations(|| assert!(css_parser::tokenize(input).count() > 1));
+
+    // idents, whitespace, symbols, numbers and hashes don't allocate
+    assert_eq!(count(&input), 0);
+
+    // escapes are decoded into owned values, the copy and at most one reallocation per token
+    let escaped = ".md\\:flex ".repeat(1000);
+    let allocated = count(&escaped);
+    assert!((1000..=2000).contains(&allocated), "{} allocations", allocated);
+}